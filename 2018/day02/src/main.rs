@@ -1,21 +1,42 @@
+use std::collections::HashMap;
 use std::io::{stdin, Read};
 
 fn main() {
+    // Part 2 looks for ids that differ in exactly this many positions.
+    let distance = match std::env::args().nth(1) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid distance {}", arg);
+            std::process::exit(1);
+        }),
+        None => 1,
+    };
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Could not read stdin");
 
-    let (checksum, common) = run(&input);
+    let (checksum, pairs) = run(&input, distance);
 
     println!("Part 1. Checksum: {}", checksum);
-    println!("Part 2. Common {}", common.unwrap_or_else(|| "None".into()));
+    println!(
+        "Part 2. Common {}",
+        pairs.first().map_or_else(|| "None".into(), Pair::common)
+    );
+
+    if pairs.len() > 1 {
+        for pair in &pairs {
+            let positions: Vec<String> = pair.positions.iter().map(|p| p.to_string()).collect();
+            println!("{} {}: {}", pair.first, pair.second, positions.join(", "));
+        }
+    }
 }
 
-fn run(input: &str) -> (u32, Option<String>) {
+/// The checksum, and every pair of ids `distance` positions apart.
+fn run(input: &str, distance: usize) -> (u32, Vec<Pair<'_>>) {
     let lines: Vec<&str> = input.split_whitespace().collect();
 
-    (part_1(&lines), part_2(&lines))
+    (part_1(&lines), close_pairs(&lines, distance))
 }
 
 fn part_1(lines: &[&str]) -> u32 {
@@ -25,11 +46,11 @@ fn part_1(lines: &[&str]) -> u32 {
         let mut ascii_char_count = [0; 256];
         line.bytes().for_each(|b| ascii_char_count[b as usize] += 1);
 
-        if ascii_char_count.iter().any(|c| *c == 2) {
+        if ascii_char_count.contains(&2) {
             two += 1;
         }
 
-        if ascii_char_count.iter().any(|c| *c == 3) {
+        if ascii_char_count.contains(&3) {
             three += 1;
         }
     }
@@ -37,29 +58,101 @@ fn part_1(lines: &[&str]) -> u32 {
     two * three
 }
 
-fn part_2(lines: &[&str]) -> Option<String> {
-    for (index, line_1) in lines.iter().enumerate() {
-        for line_2 in lines.iter().skip(index) {
-            let diff_chars = line_1
-                .chars()
-                .zip(line_2.chars())
-                .filter(|(fst, snd)| fst != snd)
-                .count();
-
-            if diff_chars == 1 {
-                let common: String = line_1
-                    .chars()
-                    .zip(line_2.chars())
-                    .filter(|(fst, snd)| fst == snd)
-                    .map(|x| x.0)
-                    .collect();
-
-                return Some(common);
+/// Two ids of the same length that differ in a few positions.
+#[derive(Debug, PartialEq)]
+struct Pair<'a> {
+    first: &'a str,
+    second: &'a str,
+    /// Character positions where the ids differ, in order.
+    positions: Vec<usize>,
+}
+
+impl Pair<'_> {
+    /// The characters both ids share.
+    fn common(&self) -> String {
+        self.first
+            .chars()
+            .enumerate()
+            .filter(|(i, _)| self.positions.binary_search(i).is_err())
+            .map(|(_, c)| c)
+            .collect()
+    }
+}
+
+/// Every pair of ids that differ in exactly `distance` positions, in the
+/// order the ids come in.
+///
+/// Rather than comparing every id with every other one, each id is filed
+/// once under every way of blanking out `distance` of its positions. Two ids
+/// that differ in exactly those positions end up filed together, and only
+/// ids filed together are compared. With `distance` 1 that is one entry per
+/// character of input, plus the pairs found.
+fn close_pairs<'a>(ids: &[&'a str], distance: usize) -> Vec<Pair<'a>> {
+    let ids: Vec<(&str, Vec<char>)> = ids.iter().map(|id| (*id, id.chars().collect())).collect();
+    let mut filed: HashMap<(Vec<usize>, String), Vec<usize>> = HashMap::new();
+
+    for (index, (_, chars)) in ids.iter().enumerate() {
+        for blanked in combinations(chars.len(), distance) {
+            let rest: String = chars
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| blanked.binary_search(i).is_err())
+                .map(|(_, &c)| c)
+                .collect();
+
+            filed.entry((blanked, rest)).or_default().push(index);
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    for ((blanked, _), indices) in &filed {
+        for (n, &a) in indices.iter().enumerate() {
+            for &b in &indices[n + 1..] {
+                // Ids that also match in some of the blanked positions are
+                // closer than `distance`, and get left out.
+                if blanked.iter().all(|&i| ids[a].1[i] != ids[b].1[i]) {
+                    pairs.push((a, b));
+                }
             }
         }
     }
+    pairs.sort_unstable();
+
+    pairs
+        .into_iter()
+        .map(|(a, b)| Pair {
+            first: ids[a].0,
+            second: ids[b].0,
+            positions: (0..ids[a].1.len())
+                .filter(|&i| ids[a].1[i] != ids[b].1[i])
+                .collect(),
+        })
+        .collect()
+}
+
+/// All sets of `k` positions out of `0..n`, each in order.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k > n {
+        return Vec::new();
+    }
+
+    let mut all = Vec::new();
+    let mut positions: Vec<usize> = (0..k).collect();
+    loop {
+        all.push(positions.clone());
 
-    None
+        // Move the last position that still can along by one, and pack the
+        // ones after it in right behind.
+        match (0..k).rev().find(|&i| positions[i] < n - k + i) {
+            Some(i) => {
+                positions[i] += 1;
+                for j in i + 1..k {
+                    positions[j] = positions[j - 1] + 1;
+                }
+            }
+            None => return all,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -90,14 +183,120 @@ wvxyz"
 
     #[test]
     fn part1() {
-        let (checksum, _) = run(&data1());
+        let (checksum, _) = run(&data1(), 1);
         assert_eq!(12, checksum);
     }
 
     #[test]
     fn part2() {
-        let (_, common) = run(&data2());
-        assert!(common.is_some());
-        assert_eq!("fgij", common.unwrap());
+        let data = data2();
+        let (_, pairs) = run(&data, 1);
+        assert_eq!(1, pairs.len());
+        assert_eq!("fgij", pairs[0].common());
+
+        let (_, pairs) = run(&data, 2);
+        assert_eq!(1, pairs.len());
+        assert_eq!("ace", pairs[0].common());
+    }
+
+    #[test]
+    fn all_pairs() {
+        let ids = ["abcd", "abce", "xbce", "abcd", "abc", "zbcd"];
+        let pair = |first, second, positions: &[usize]| Pair {
+            first,
+            second,
+            positions: positions.to_vec(),
+        };
+
+        assert_eq!(
+            vec![
+                pair("abcd", "abce", &[3]),
+                pair("abcd", "zbcd", &[0]),
+                pair("abce", "xbce", &[0]),
+                pair("abce", "abcd", &[3]),
+                pair("abcd", "zbcd", &[0]),
+            ],
+            close_pairs(&ids, 1)
+        );
+        assert_eq!(
+            vec![
+                pair("abcd", "xbce", &[0, 3]),
+                pair("abce", "zbcd", &[0, 3]),
+                pair("xbce", "abcd", &[0, 3]),
+                pair("xbce", "zbcd", &[0, 3]),
+            ],
+            close_pairs(&ids, 2)
+        );
+        assert_eq!(vec![pair("abcd", "abcd", &[])], close_pairs(&ids, 0));
+        assert_eq!("bc", close_pairs(&ids, 2)[0].common());
+        assert!(close_pairs(&ids, 5).is_empty());
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(
+            vec![Pair {
+                first: "håll",
+                second: "hæll",
+                positions: vec![1],
+            }],
+            close_pairs(&["håll", "hæll", "hall."], 1)
+        );
+    }
+
+    #[test]
+    fn combinations() {
+        assert_eq!(vec![Vec::<usize>::new()], super::combinations(3, 0));
+        assert_eq!(
+            vec![vec![0, 1], vec![0, 2], vec![1, 2]],
+            super::combinations(3, 2)
+        );
+        assert_eq!(10, super::combinations(5, 3).len());
+        assert!(super::combinations(2, 3).is_empty());
+    }
+
+    #[test]
+    fn matches_comparing_every_pair() {
+        // Every id of five letters out of "abc", shuffled by stepping
+        // through them with a stride coprime to their number.
+        let ids: Vec<String> = (0..243)
+            .map(|n| {
+                let mut n = n * 100 % 243;
+                (0..5)
+                    .map(|_| {
+                        let c = (b'a' + (n % 3) as u8) as char;
+                        n /= 3;
+                        c
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for distance in 0..4 {
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+
+            let mut expected = Vec::new();
+            for (a, first) in ids.iter().enumerate() {
+                for second in &ids[a + 1..] {
+                    let positions: Vec<usize> = first
+                        .chars()
+                        .zip(second.chars())
+                        .enumerate()
+                        .filter(|(_, (x, y))| x != y)
+                        .map(|(i, _)| i)
+                        .collect();
+
+                    if positions.len() == distance {
+                        expected.push(Pair {
+                            first,
+                            second,
+                            positions,
+                        });
+                    }
+                }
+            }
+
+            assert_eq!(expected, close_pairs(&ids, distance), "{}", distance);
+        }
     }
 }
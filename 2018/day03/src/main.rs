@@ -1,3 +1,4 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::{stdin, Read};
 use std::str::FromStr;
 
@@ -46,57 +47,181 @@ impl FromStr for Claim {
     }
 }
 
-fn run(input: &str) -> (u32, u32) {
-    let claims: Vec<Claim> = input.lines().flat_map(str::parse).collect();
+impl Claim {
+    fn left(&self) -> i64 {
+        self.x.into()
+    }
 
-    let mut max_x = 0;
-    let mut max_y = 0;
-    for c in &claims {
-        max_x = max_x.max(c.x + c.width as i32);
-        max_y = max_y.max(c.y + c.height as i32);
+    fn right(&self) -> i64 {
+        self.left() + i64::from(self.width)
     }
 
-    let mut coordinate_claims = vec![vec![0; max_x as usize]; max_y as usize];
+    fn top(&self) -> i64 {
+        self.y.into()
+    }
 
-    for claim in &claims {
-        for y in (claim.y..).take(claim.height as usize) {
-            for x in (claim.x..).take(claim.width as usize) {
-                coordinate_claims[y as usize][x as usize] += 1;
-            }
-        }
+    fn bottom(&self) -> i64 {
+        self.top() + i64::from(self.height)
     }
 
-    let overlapping = coordinate_claims
+    fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    fn overlaps(&self, other: &Claim) -> bool {
+        self.left() < other.right()
+            && other.left() < self.right()
+            && self.top() < other.bottom()
+            && other.top() < self.bottom()
+    }
+}
+
+/// Every claim by id, with the ids of all the claims it overlaps.
+type Graph = BTreeMap<u32, BTreeSet<u32>>;
+
+/// Square inches covered by two or more claims. Sweeps a line across the
+/// fabric from left to right, stopping only where a claim starts or ends,
+/// and keeps count of how many claims cover each band of rows between
+/// the top and bottom edges of any claim. Nothing is allocated per square
+/// inch, so the fabric can be as large and sparse as the claims like.
+fn overlapping_area(claims: &[Claim]) -> u64 {
+    let claims: Vec<&Claim> = claims.iter().filter(|c| !c.is_empty()).collect();
+
+    let mut rows: Vec<i64> = claims
         .iter()
-        .map(|row| row.iter().filter(|count| **count > 1).count())
-        .sum::<usize>() as u32;
+        .flat_map(|c| vec![c.top(), c.bottom()])
+        .collect();
+    rows.sort_unstable();
+    rows.dedup();
+    let band = |row| rows.binary_search(&row).unwrap();
 
-    let lonely = claims
+    // Where every claim starts and ends along the sweep, and which bands it
+    // covers.
+    let mut events: Vec<(i64, i32, usize, usize)> = claims
         .iter()
-        .find(|claim| {
-            let contested = (claim.y..).take(claim.height as usize).any(|row| {
-                (claim.x..)
-                    .take(claim.width as usize)
-                    .any(|column| coordinate_claims[row as usize][column as usize] > 1)
-            });
-
-            !contested
+        .flat_map(|c| {
+            let (top, bottom) = (band(c.top()), band(c.bottom()));
+            vec![(c.left(), 1, top, bottom), (c.right(), -1, top, bottom)]
         })
-        .expect("No lone claim found");
+        .collect();
+    events.sort_unstable();
+
+    let mut counts = vec![0; rows.len().saturating_sub(1)];
+    let mut contested = 0;
+    let mut area = 0;
+    let mut last = events.first().map_or(0, |e| e.0);
+
+    for (x, change, top, bottom) in events {
+        area += contested * (x - last) as u64;
+        last = x;
+
+        for (i, count) in counts.iter_mut().enumerate().take(bottom).skip(top) {
+            let height = (rows[i + 1] - rows[i]) as u64;
+            match (*count, *count + change) {
+                (1, 2) => contested += height,
+                (2, 1) => contested -= height,
+                _ => {}
+            }
+            *count += change;
+        }
+    }
+
+    area
+}
+
+/// Which claims overlap which. Claims are swept from left to right, so each
+/// one is only checked against the claims that have started but not yet
+/// ended.
+fn overlap_graph(claims: &[Claim]) -> Graph {
+    let mut graph: Graph = claims.iter().map(|c| (c.id, BTreeSet::new())).collect();
+
+    let mut order: Vec<&Claim> = claims.iter().filter(|c| !c.is_empty()).collect();
+    order.sort_unstable_by_key(|c| c.left());
+
+    let mut active: Vec<&Claim> = Vec::new();
+    for claim in order {
+        active.retain(|a| a.right() > claim.left());
+
+        for other in &active {
+            if claim.overlaps(other) {
+                graph.get_mut(&claim.id).unwrap().insert(other.id);
+                graph.get_mut(&other.id).unwrap().insert(claim.id);
+            }
+        }
+
+        active.push(claim);
+    }
+
+    graph
+}
+
+/// Ids of every claim that no other claim overlaps.
+fn lonely(graph: &Graph) -> Vec<u32> {
+    graph
+        .iter()
+        .filter(|(_, others)| others.is_empty())
+        .map(|(&id, _)| id)
+        .collect()
+}
+
+/// The area covered more than once, and which claims overlap.
+fn run(input: &str) -> (u64, Graph) {
+    let claims: Vec<Claim> = input.lines().flat_map(str::parse).collect();
+
+    (overlapping_area(&claims), overlap_graph(&claims))
+}
+
+/// Command line flags. `--graph` lists every pair of claims that overlap.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    graph: bool,
+}
+
+impl Options {
+    fn from_args(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        for arg in args {
+            match arg.as_str() {
+                "--graph" => options.graph = true,
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
 
-    (overlapping, lonely.id)
+        Ok(options)
+    }
 }
 
 fn main() {
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let (overlapping, id) = run(&input);
+    let (overlapping, graph) = run(&input);
 
     println!("Overlapping squares: {}", overlapping);
-    println!("Single area without overlap: #{}", id);
+    match lonely(&graph).as_slice() {
+        [] => println!("Every claim overlaps another"),
+        [id] => println!("Single area without overlap: #{}", id),
+        ids => {
+            let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+            println!("Areas without overlap: {}", ids.join(", "));
+        }
+    }
+
+    if options.graph {
+        for (id, others) in graph {
+            for other in others.range(id + 1..) {
+                println!("#{} overlaps #{}", id, other);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -118,7 +243,106 @@ mod tests {
 
     #[test]
     fn part2() {
-        let (_, lone_id) = run(&data());
-        assert_eq!(3, lone_id);
+        let (_, graph) = run(&data());
+        assert_eq!(vec![3], lonely(&graph));
+    }
+
+    fn claims(input: &str) -> Vec<Claim> {
+        input.lines().flat_map(str::parse).collect()
+    }
+
+    #[test]
+    fn negative_and_far_apart() {
+        let claims = claims(
+            "#1 @ -5,-5: 4x4
+            #2 @ -3,-3: 4x4
+            #3 @ 2000000000,2000000000: 100000x100000
+            #4 @ 2000050000,2000000000: 100000x100000
+            #5 @ 0,0: 0x10",
+        );
+
+        assert_eq!(4 + 50_000 * 100_000, overlapping_area(&claims));
+
+        let graph = overlap_graph(&claims);
+        let others = |id| graph[&id].iter().copied().collect::<Vec<_>>();
+        assert_eq!(vec![2], others(1));
+        assert_eq!(vec![3], others(4));
+        // Covers nothing, so overlaps nothing.
+        assert_eq!(Vec::<u32>::new(), others(5));
+    }
+
+    #[test]
+    fn graph() {
+        let claims = claims(
+            "#1 @ 0,0: 10x10
+            #2 @ 2,2: 2x2
+            #3 @ 6,6: 2x2
+            #4 @ 10,0: 5x5
+            #5 @ 9,9: 1x1",
+        );
+        let graph = overlap_graph(&claims);
+        let others = |id| graph[&id].iter().copied().collect::<Vec<_>>();
+
+        assert_eq!(vec![2, 3, 5], others(1));
+        assert_eq!(vec![1], others(2));
+        // Touching an edge is not overlapping.
+        assert_eq!(Vec::<u32>::new(), others(4));
+        let (overlapping, graph) = run(&claims_input(&claims));
+        assert_eq!(4 + 4 + 1, overlapping);
+        assert_eq!(vec![4], lonely(&graph));
+    }
+
+    fn claims_input(claims: &[Claim]) -> String {
+        claims
+            .iter()
+            .map(|c| format!("#{} @ {},{}: {}x{}\n", c.id, c.x, c.y, c.width, c.height))
+            .collect()
+    }
+
+    #[test]
+    fn matches_counting_every_square() {
+        for round in 0..50 {
+            // Scattered by multiplying with numbers coprime to the ranges.
+            let claims: Vec<Claim> = (1..=20)
+                .map(|id| Claim {
+                    id,
+                    x: ((id * 7 + round * 13) % 30) as i32 - 10,
+                    y: ((id * 11 + round * 17) % 29) as i32 - 10,
+                    width: (id * 3 + round) % 8,
+                    height: (id * 5 + round * 3) % 7,
+                })
+                .collect();
+
+            let mut counts = vec![vec![0; 50]; 50];
+            for c in &claims {
+                for y in c.top()..c.bottom() {
+                    for x in c.left()..c.right() {
+                        counts[(y + 10) as usize][(x + 10) as usize] += 1;
+                    }
+                }
+            }
+            let expected = counts.iter().flatten().filter(|&&n| n > 1).count() as u64;
+            assert_eq!(expected, overlapping_area(&claims));
+
+            let graph = overlap_graph(&claims);
+            for a in &claims {
+                for b in &claims {
+                    let overlap = a.id != b.id && !a.is_empty() && !b.is_empty() && a.overlaps(b);
+                    assert_eq!(overlap, graph[&a.id].contains(&b.id));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        assert!(
+            Options::from_args(args("--graph").into_iter())
+                .unwrap()
+                .graph
+        );
+        assert!(Options::from_args(args("--verbose").into_iter()).is_err());
     }
 }
@@ -1,100 +1,274 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::io::{stdin, Read};
 
-fn remove_reactions(polymer: &[u8]) -> Vec<u8> {
-    let mut ret: Vec<u8> = Vec::with_capacity(polymer.len());
-
-    let mut units_reacted = false;
-    let mut iter = polymer.iter();
-
-    // Mess. Basically try to get a next byte to compare with and
-    // if they are equal, check if they also react.
-    let mut current = iter.next().unwrap();
-    loop {
-        if let Some(next) = iter.next() {
-            if units_react(*current, *next) {
-                // If they are equal and react, set a flag so we can do another
-                // pass recusively after the loop, advance the iterator or break.
-                units_reacted = true;
-                match iter.next() {
-                    Some(b) => current = b,
-                    None => break,
-                };
-            } else {
-                // If equal but no reaction, push current byte to return vec,
-                // set current = next.
-                ret.push(*current);
-                current = next;
-            }
-        } else {
-            // If no equality, push the current char, advance the iterator or break.
-            ret.push(*current);
-            match iter.next() {
-                Some(b) => current = b,
-                None => break,
+/// Which units react with each other.
+#[derive(Debug, PartialEq)]
+enum Rules {
+    /// The same letter in opposite cases, in any alphabet that has case.
+    CasePairs,
+    /// Pairs of symbols read from a table. Units react in either order.
+    Table {
+        pairs: HashSet<(char, char)>,
+        /// The smallest symbol connected to every symbol through pairs, so
+        /// that everything that reacts together is removed together.
+        kinds: HashMap<char, char>,
+    },
+}
+
+impl Rules {
+    /// One pair of symbols per line, separated by whitespace.
+    fn parse_table(input: &str) -> Result<Rules, String> {
+        let mut pairs = HashSet::new();
+        let mut partners: HashMap<char, Vec<char>> = HashMap::new();
+
+        for (i, line) in input.lines().enumerate() {
+            let symbols: Vec<&str> = line.split_whitespace().collect();
+            let (a, b) = match symbols[..] {
+                [] => continue,
+                [a, b] => match (single_char(a), single_char(b)) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => return Err(format!("Line {}: Symbols must be one character", i + 1)),
+                },
+                _ => return Err(format!("Line {}: Expected two symbols", i + 1)),
             };
+
+            pairs.insert((a, b));
+            pairs.insert((b, a));
+            partners.entry(a).or_default().push(b);
+            partners.entry(b).or_default().push(a);
+        }
+
+        // Flood every group of connected symbols, smallest symbol first.
+        let mut kinds = HashMap::new();
+        let symbols: BTreeSet<char> = partners.keys().copied().collect();
+        for &symbol in &symbols {
+            if kinds.contains_key(&symbol) {
+                continue;
+            }
+
+            let mut queue = vec![symbol];
+            kinds.insert(symbol, symbol);
+            while let Some(s) = queue.pop() {
+                for &partner in &partners[&s] {
+                    if kinds.insert(partner, symbol).is_none() {
+                        queue.push(partner);
+                    }
+                }
+            }
         }
+
+        Ok(Rules::Table { pairs, kinds })
+    }
+
+    fn react(&self, u1: char, u2: char) -> bool {
+        match self {
+            Rules::CasePairs => {
+                u1 != u2
+                    && u1.is_lowercase() != u2.is_lowercase()
+                    && u1.to_lowercase().eq(u2.to_lowercase())
+            }
+            Rules::Table { pairs, .. } => pairs.contains(&(u1, u2)),
+        }
+    }
+
+    /// The kind of unit removed together with `unit` in part 2.
+    fn kind(&self, unit: char) -> char {
+        match self {
+            Rules::CasePairs => unit.to_lowercase().next().unwrap_or(unit),
+            Rules::Table { kinds, .. } => kinds.get(&unit).copied().unwrap_or(unit),
+        }
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
     }
+}
+
+/// Two units that reacted, by their positions in the original polymer.
+#[derive(Debug, PartialEq)]
+struct Reaction {
+    left: usize,
+    right: usize,
+}
 
-    if !units_reacted {
-        ret
-    } else {
-        remove_reactions(&ret)
+/// Reacts the whole polymer in one pass. Every unit either reacts with the
+/// last unit kept so far, which may itself be exposed by an earlier reaction,
+/// or is kept on top of the stack of survivors. Reactions are reported in
+/// the order they happen.
+fn reduce(polymer: &[char], rules: &Rules, mut on_reaction: impl FnMut(Reaction)) -> Vec<char> {
+    // Positions of the units kept so far.
+    let mut kept: Vec<usize> = Vec::with_capacity(polymer.len());
+
+    for (i, &unit) in polymer.iter().enumerate() {
+        match kept.last() {
+            Some(&last) if rules.react(polymer[last], unit) => {
+                kept.pop();
+                on_reaction(Reaction {
+                    left: last,
+                    right: i,
+                });
+            }
+            _ => kept.push(i),
+        }
     }
+
+    kept.into_iter().map(|i| polymer[i]).collect()
 }
 
-fn units_react(u1: u8, u2: u8) -> bool {
-    u1.eq_ignore_ascii_case(&u2) && u1.is_ascii_lowercase() != u2.is_ascii_lowercase()
+fn remove_reactions(polymer: &[char], rules: &Rules) -> Vec<char> {
+    reduce(polymer, rules, |_| {})
 }
 
-fn polymer_without_unit(polymer: &[u8], unit: u8) -> Vec<u8> {
+/// The reduced polymer together with every reaction that got it there.
+fn trace(polymer: &[char], rules: &Rules) -> (Vec<char>, Vec<Reaction>) {
+    let mut reactions = Vec::new();
+    let result = reduce(polymer, rules, |r| reactions.push(r));
+
+    (result, reactions)
+}
+
+fn polymer_without_unit(polymer: &[char], kind: char, rules: &Rules) -> Vec<char> {
     polymer
         .iter()
-        .filter(|x| !x.eq_ignore_ascii_case(&unit))
+        .filter(|&&x| rules.kind(x) != kind)
         .cloned()
         .collect()
 }
 
-fn run(input: &str) -> (usize, usize) {
-    let input = input.trim().as_bytes();
-
-    let result = remove_reactions(&input);
+fn run(input: &str, rules: &Rules) -> (usize, usize) {
+    let input: Vec<char> = input.trim().chars().collect();
 
-    // Collect all unique (case insensitive) units to remove.
-    // Case doesn't matter so the resulting set size can be shrunk even further.
-    let bytes: HashSet<u8> = input.iter().map(u8::to_ascii_lowercase).collect();
+    let result = remove_reactions(&input, rules);
 
-    let min = bytes
+    // With case pairs, removing a unit and then reacting gives the same
+    // polymer whether or not the rest reacted first, so start from the much
+    // shorter result. A table can make the order matter, as a unit that has
+    // reacted away might instead have reacted with something else once the
+    // removed kind is gone, so start from the whole input there. The kinds
+    // are taken from the whole input either way, as removing one that has
+    // reacted away entirely still counts.
+    let start = match rules {
+        Rules::CasePairs => &result,
+        Rules::Table { .. } => &input,
+    };
+    let kinds: Vec<char> = input
         .iter()
-        .map(|b| remove_reactions(&polymer_without_unit(&input, *b)).len())
-        .min()
-        .unwrap();
+        .map(|&u| rules.kind(u))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let thread_count = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(kinds.len())
+        .max(1);
+    let min = std::thread::scope(|scope| {
+        let kinds = &kinds;
+        let handles: Vec<_> = (0..thread_count)
+            .map(|first| {
+                scope.spawn(move || {
+                    kinds
+                        .iter()
+                        .skip(first)
+                        .step_by(thread_count)
+                        .map(|&kind| {
+                            remove_reactions(&polymer_without_unit(start, kind, rules), rules).len()
+                        })
+                        .min()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .min()
+            .unwrap_or(result.len())
+    });
 
     (result.len(), min)
 }
 
+/// Command line flags. `--rules FILE` reads the pairs of units that react
+/// from a file and `--trace` lists every reaction.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    rules: Option<String>,
+    trace: bool,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+
+            match arg.as_str() {
+                "--rules" => options.rules = Some(value()?),
+                "--trace" => options.trace = true,
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
 fn main() {
+    let fail = |e: String| -> ! {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    };
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| fail(e));
+
+    let rules = match &options.rules {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))
+            .and_then(|s| Rules::parse_table(&s))
+            .unwrap_or_else(|e| fail(e)),
+        None => Rules::CasePairs,
+    };
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let (length, smallest) = run(&input);
+    if options.trace {
+        let polymer: Vec<char> = input.trim().chars().collect();
+        let (result, reactions) = trace(&polymer, &rules);
+
+        for Reaction { left, right } in reactions {
+            println!("{} {}: {}{}", left, right, polymer[left], polymer[right]);
+        }
+        println!("Result: {}", result.iter().collect::<String>());
+    }
+
+    let (length, smallest) = run(&input, &rules);
     println!("Part 1. Length: {}", length);
     println!("Part 2. Smallest polymer: {}", smallest);
 }
 
 #[cfg(test)]
 mod tests {
-    use super::run;
+    use super::*;
 
     fn data() -> String {
         String::from("dabAcCaCBAcCcaDA")
     }
 
+    fn remove_reactions_with(input: &str, rules: &Rules) -> String {
+        let input: Vec<char> = input.chars().collect();
+        super::remove_reactions(&input, rules).into_iter().collect()
+    }
+
     fn remove_reactions(input: &str) -> String {
-        let ret = super::remove_reactions(input.as_bytes());
-        String::from_utf8(ret).unwrap()
+        remove_reactions_with(input, &Rules::CasePairs)
     }
 
     #[test]
@@ -105,17 +279,97 @@ mod tests {
         assert_eq!("abCBAcaD", remove_reactions("DdabAcCaCBAcCcaDAaBbCc"));
 
         assert_eq!("AC", remove_reactions("AaACcC"));
+
+        // Reactions expose units that react in turn.
+        assert_eq!("", remove_reactions("abcdDCBA"));
+        assert_eq!("", remove_reactions(""));
+    }
+
+    #[test]
+    fn long_polymer() {
+        // Nested all the way down, which took a pass and a recursion per
+        // level before.
+        let half: String = (0..100_000)
+            .map(|i| (b'a' + (i % 26) as u8) as char)
+            .collect();
+        let polymer: String = half
+            .chars()
+            .chain(half.to_uppercase().chars().rev())
+            .collect();
+
+        assert_eq!("", remove_reactions(&polymer));
+        assert_eq!((0, 0), run(&polymer, &Rules::CasePairs));
     }
 
     #[test]
     fn part1() {
-        let (length, _) = run(&data());
+        let (length, _) = run(&data(), &Rules::CasePairs);
         assert_eq!(10, length);
     }
 
     #[test]
     fn part2() {
-        let (_, smallest) = run(&data());
+        let (_, smallest) = run(&data(), &Rules::CasePairs);
         assert_eq!(4, smallest);
     }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!("", remove_reactions("äÅåÄ"));
+        assert_eq!("ÄÄ", remove_reactions("ÄωΩÄ"));
+        // Without case there is nothing to react with.
+        assert_eq!("猫猫", remove_reactions("猫猫"));
+        assert_eq!((0, 0), run("ωäÄΩ", &Rules::CasePairs));
+    }
+
+    #[test]
+    fn table() {
+        let rules = Rules::parse_table("+ -\n\n( )\n) ]\n").unwrap();
+
+        assert_eq!("+(", remove_reactions_with("+-+(])", &rules));
+        assert_eq!("", remove_reactions_with("-+", &rules));
+        assert_eq!("aA", remove_reactions_with("aA", &rules));
+
+        // ( ) and ] react together, so they are removed together.
+        assert_eq!('(', rules.kind(']'));
+        assert_eq!('+', rules.kind('-'));
+        assert_eq!('x', rules.kind('x'));
+        assert_eq!((6, 1), run("+(+)-]", &rules));
+
+        // Without x, a reacts with b and c with d, rather than leaving them
+        // behind when b and c react.
+        let rules = Rules::parse_table("a b\nb c\nc d").unwrap();
+        assert_eq!("", remove_reactions_with("abcd", &rules));
+        assert_eq!("axd", remove_reactions_with("axbcd", &rules));
+        assert_eq!((3, 0), run("axbcd", &rules));
+
+        assert!(Rules::parse_table("+").is_err());
+        assert!(Rules::parse_table("+ -- ").is_err());
+        assert!(Rules::parse_table("a b c").is_err());
+    }
+
+    #[test]
+    fn reaction_trace() {
+        let polymer: Vec<char> = data().chars().collect();
+        let (result, reactions) = trace(&polymer, &Rules::CasePairs);
+        let reaction = |left, right| Reaction { left, right };
+
+        assert_eq!("dabCBAcaDA", result.iter().collect::<String>());
+        assert_eq!(
+            vec![reaction(4, 5), reaction(3, 6), reaction(10, 11)],
+            reactions
+        );
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let options = Options::from_args(args("--rules pairs.txt --trace").into_iter()).unwrap();
+        assert_eq!(Some("pairs.txt".to_string()), options.rules);
+        assert!(options.trace);
+
+        assert!(Options::from_args(args("--rules").into_iter()).is_err());
+        assert!(Options::from_args(args("--verbose").into_iter()).is_err());
+    }
 }
@@ -1,150 +1,483 @@
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-use std::io::{stdin, Read};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, stdin, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Clone, Debug)]
-enum Point {
-    Empty,
-    Coordinate { x: i32, y: i32 },
-    ClosestTo { x: i32, y: i32, distance: u32 },
-    ClosestToMany { distance: u32 },
+type Coordinate = (i32, i32);
+
+fn parse_coordinates(input: &str) -> Result<Vec<Coordinate>, String> {
+    let mut coordinates = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let number = |s: Option<&str>| {
+                s.and_then(|s| s.trim().parse().ok()).ok_or(format!(
+                    "Line {}: Expected X, Y but got '{}'",
+                    i + 1,
+                    line
+                ))
+            };
+            let mut co = line.split(',');
+            let coordinate = (number(co.next())?, number(co.next())?);
+
+            match co.next() {
+                Some(_) => Err(format!("Line {}: Expected X, Y but got '{}'", i + 1, line)),
+                None => Ok(coordinate),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    coordinates.sort_unstable();
+    coordinates.dedup();
+
+    match coordinates.is_empty() {
+        true => Err("No coordinates".to_string()),
+        false => Ok(coordinates),
+    }
 }
 
-fn manhattan_distance((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> u32 {
-    let distance = (x2 - x1).abs() + (y2 - y1).abs();
-    distance as u32
+/// The smallest box around some coordinates, grown by a margin on every side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Bounds {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
 }
 
-fn get_point(x: i32, y: i32, coordinates: &HashSet<(i32, i32)>) -> Point {
-    coordinates
-        .iter()
-        .zip(coordinates.iter().map(|&c| manhattan_distance(c, (x, y))))
-        .fold(
-            Point::Empty,
-            |acc, (&(cur_x, cur_y), cur_distance)| match acc {
-                Point::Empty => Point::ClosestTo {
-                    x: cur_x,
-                    y: cur_y,
-                    distance: cur_distance,
-                },
-
-                Point::ClosestTo { x, y, distance } => match cur_distance.cmp(&distance) {
-                    Ordering::Equal => Point::ClosestToMany {
-                        distance: cur_distance,
-                    },
-                    Ordering::Less => Point::ClosestTo {
-                        x: cur_x,
-                        y: cur_y,
-                        distance: cur_distance,
-                    },
-                    Ordering::Greater => Point::ClosestTo { x, y, distance },
-                },
-
-                Point::ClosestToMany { distance } => match cur_distance.cmp(&distance) {
-                    Ordering::Equal => Point::ClosestToMany {
-                        distance: cur_distance,
-                    },
-                    Ordering::Less => Point::ClosestTo {
-                        x: cur_x,
-                        y: cur_y,
-                        distance: cur_distance,
-                    },
-                    Ordering::Greater => Point::ClosestToMany { distance },
-                },
-
-                point => point,
-            },
-        )
+impl Bounds {
+    fn around(coordinates: &[Coordinate], margin: i32) -> Self {
+        let (xs, ys) = (
+            coordinates.iter().map(|c| c.0),
+            coordinates.iter().map(|c| c.1),
+        );
+
+        Bounds {
+            min_x: xs.clone().min().unwrap() - margin,
+            min_y: ys.clone().min().unwrap() - margin,
+            max_x: xs.max().unwrap() + margin,
+            max_y: ys.max().unwrap() + margin,
+        }
+    }
+
+    fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+
+    fn index(&self, (x, y): Coordinate) -> usize {
+        (y - self.min_y) as usize * self.width() + (x - self.min_x) as usize
+    }
+
+    fn on_edge(&self, (x, y): Coordinate) -> bool {
+        x == self.min_x || x == self.max_x || y == self.min_y || y == self.max_y
+    }
+
+    fn cells(&self) -> impl Iterator<Item = Coordinate> {
+        let (min_x, max_x) = (self.min_x, self.max_x);
+        (self.min_y..=self.max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+    }
 }
 
-fn run(input: &str, boundary: u32) -> (i32, u32) {
-    let coordinates: HashSet<(i32, i32)> = input
-        .lines()
-        .map(|line| {
-            let mut co = line.split(", ");
-            let x = co.next().unwrap().parse().unwrap();
-            let y = co.next().unwrap().parse().unwrap();
-            (x, y)
-        })
-        .collect();
-
-    let mut max_x = 0;
-    let mut max_y = 0;
-    coordinates.iter().for_each(|&(x, y)| {
-        max_x = max_x.max(x);
-        max_y = max_y.max(y);
-    });
-
-    let mut grid = vec![vec![Point::Empty; (max_x + 1) as usize]; (max_y + 1) as usize];
-
-    // enumerate() yields index as usize, zip to avoid recasting
-    for (y, row) in (0..).zip(grid.iter_mut()) {
-        for (x, column) in (0..).zip(row.iter_mut()) {
-            if coordinates.contains(&(x, y)) {
-                *column = Point::Coordinate { x, y };
-            } else {
-                *column = get_point(x, y, &coordinates);
+/// Which coordinate a cell is closest to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Owner {
+    Unreached,
+    /// Index of the one closest coordinate.
+    Coordinate(usize),
+    /// Two or more coordinates are equally close.
+    Tie,
+}
+
+/// The area closest to every coordinate, within a box around them.
+struct Voronoi {
+    coordinates: Vec<Coordinate>,
+    bounds: Bounds,
+    /// Owner of every cell, row by row.
+    owners: Vec<Owner>,
+}
+
+impl Voronoi {
+    /// Grows all areas at once, a step of distance at a time, from every
+    /// coordinate. A cell is closest to whatever its neighbours one step
+    /// nearer are closest to, so a tie spreads just like an owner does.
+    /// Manhattan shortest paths between a coordinate and a cell never leave
+    /// the box around them, so the box gives the exact answer.
+    fn new(coordinates: &[Coordinate], margin: i32) -> Self {
+        let bounds = Bounds::around(coordinates, margin.max(0));
+        let mut owners = vec![Owner::Unreached; bounds.width() * bounds.height()];
+        let mut distances = vec![0; owners.len()];
+        let mut queue = VecDeque::new();
+
+        for (i, &c) in coordinates.iter().enumerate() {
+            owners[bounds.index(c)] = Owner::Coordinate(i);
+            queue.push_back(c);
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            let cell = bounds.index((x, y));
+            let (owner, distance) = (owners[cell], distances[cell] + 1);
+
+            for next in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if !(bounds.min_x..=bounds.max_x).contains(&next.0)
+                    || !(bounds.min_y..=bounds.max_y).contains(&next.1)
+                {
+                    continue;
+                }
+
+                let n = bounds.index(next);
+                match owners[n] {
+                    Owner::Unreached => {
+                        owners[n] = owner;
+                        distances[n] = distance;
+                        queue.push_back(next);
+                    }
+                    other if distances[n] == distance && other != owner => owners[n] = Owner::Tie,
+                    _ => {}
+                }
             }
         }
-    }
 
-    let mut areas: HashMap<(i32, i32), Option<i32>> =
-        coordinates.iter().map(|&c| (c, Some(0))).collect();
+        Voronoi {
+            coordinates: coordinates.to_vec(),
+            bounds,
+            owners,
+        }
+    }
 
-    for (y, row) in grid.iter().enumerate() {
-        for (x, column) in row.iter().enumerate() {
-            let coordinate = match *column {
-                Point::Coordinate { x, y } | Point::ClosestTo { x, y, .. } => (x, y),
-                _ => continue,
-            };
+    fn owner(&self, cell: Coordinate) -> Owner {
+        self.owners[self.bounds.index(cell)]
+    }
 
-            let entry = areas.get_mut(&coordinate).unwrap();
+    /// Size of the area closest to every coordinate, or `None` if it goes on
+    /// forever. Walking out of the box away from every coordinate adds the
+    /// same distance to all of them, so the owner of an edge cell keeps
+    /// every cell beyond it. And any cell out there has the same owner as
+    /// the closest cell on the edge, so areas that never reach the edge are
+    /// finite.
+    fn areas(&self) -> Vec<Option<usize>> {
+        let mut areas = vec![Some(0); self.coordinates.len()];
 
-            if let Some(count) = entry {
-                // Edge of grid == infinite area
-                if x == 0 || y == 0 || x == max_x as usize || y == max_y as usize {
-                    *entry = None;
-                } else {
-                    *count += 1;
+        for cell in self.bounds.cells() {
+            if let Owner::Coordinate(i) = self.owner(cell) {
+                match self.bounds.on_edge(cell) {
+                    true => areas[i] = None,
+                    false => {
+                        if let Some(area) = &mut areas[i] {
+                            *area += 1;
+                        }
+                    }
                 }
             }
         }
+
+        areas
     }
+}
 
-    let largest = areas.values().flatten().max().unwrap();
+/// Total distance from every position in `from..=to` along one axis to all
+/// the `values` on it.
+fn axis_totals(values: impl Iterator<Item = i32>, from: i64, to: i64) -> Vec<u64> {
+    let mut values: Vec<i64> = values.map(i64::from).collect();
+    values.sort_unstable();
 
-    let mut area = 0;
-    for x in 0..=max_x {
-        for y in 0..=max_y {
-            let sum: u32 = coordinates
-                .iter()
-                .map(|&c| manhattan_distance(c, (x, y)))
-                .sum();
+    let mut total: u64 = values.iter().map(|&v| (v - from).unsigned_abs()).sum();
+    let mut below = values.partition_point(|&v| v < from);
+    let mut totals = Vec::new();
+
+    for position in from..=to {
+        totals.push(total);
+
+        // One step right moves away from everything at or left of here and
+        // towards everything else.
+        while below < values.len() && values[below] <= position {
+            below += 1;
+        }
+        total = total + below as u64 - (values.len() - below) as u64;
+    }
+
+    totals
+}
+
+/// Most positions along one axis that the region is counted over. Every
+/// position takes one total, so this bounds the memory used.
+const MAX_AXIS: i64 = 10_000_000;
+
+/// The totals along one axis that are below `boundary`. The total grows by
+/// at least one per step away from the values, so nothing further than
+/// `boundary` beyond them can count.
+fn axis_sums(values: impl Iterator<Item = i32> + Clone, boundary: u64) -> Result<Vec<u64>, String> {
+    let (first, last) = (values.clone().min().unwrap(), values.clone().max().unwrap());
+    let reach = (boundary / values.clone().count() as u64)
+        .checked_add(1)
+        .and_then(|reach| i64::try_from(reach).ok());
+
+    let from = reach.and_then(|reach| i64::from(first).checked_sub(reach));
+    let to = reach.and_then(|reach| i64::from(last).checked_add(reach));
+    let (from, to) = match (from, to) {
+        (Some(from), Some(to)) if to.checked_sub(from).filter(|&len| len < MAX_AXIS).is_some() => {
+            (from, to)
+        }
+        _ => return Err(format!("Boundary {} reaches too far to count", boundary)),
+    };
+
+    Ok(axis_totals(values, from, to)
+        .into_iter()
+        .filter(|&total| total < boundary)
+        .collect())
+}
+
+/// Number of cells whose total distance to all coordinates is below
+/// `boundary`, anywhere on the grid. Manhattan distance is the sum of the
+/// distances along each axis, so the total for a cell is the total for its
+/// column plus the total for its row, and every column can count its rows
+/// with one search through the sorted row totals.
+fn region_size(coordinates: &[Coordinate], boundary: u64) -> Result<u64, String> {
+    let columns = axis_sums(coordinates.iter().map(|c| c.0), boundary)?;
+    let mut rows = axis_sums(coordinates.iter().map(|c| c.1), boundary)?;
+    rows.sort_unstable();
+
+    Ok(columns
+        .iter()
+        .map(|&column| rows.partition_point(|&row| column + row < boundary) as u64)
+        .sum())
+}
+
+/// Colour of every coordinate's area, spread around the colour wheel.
+fn area_colour(i: usize) -> [u8; 3] {
+    let hue = (i as f64 * 0.618_034).fract() * 6.0;
+    let fall = |offset: f64| {
+        let distance = ((hue - offset).rem_euclid(6.0) - 3.0).abs();
+        (64.0 + 191.0 * (distance - 1.0).clamp(0.0, 1.0)) as u8
+    };
+
+    [fall(0.0), fall(4.0), fall(2.0)]
+}
+
+/// Colour of an area that goes on forever, the colour of the area washed
+/// out to a grey of the same brightness.
+fn infinite_colour(i: usize) -> [u8; 3] {
+    let [r, g, b] = area_colour(i);
+    let grey = ((u32::from(r) + u32::from(g) + u32::from(b)) / 3 / 2 + 48) as u8;
+
+    [grey; 3]
+}
+
+const TIE: [u8; 3] = [0, 0, 0];
+const COORDINATE: [u8; 3] = [0xff, 0xff, 0xff];
+const OUTLINE: [u8; 3] = [0xff, 0x20, 0x20];
+
+impl Voronoi {
+    /// One pixel per cell of the box, row by row. Cells inside the region
+    /// closer than `boundary` to everything in total are outlined where they
+    /// border cells outside it.
+    fn pixels(&self, boundary: u64) -> Vec<[u8; 3]> {
+        let b = &self.bounds;
+        let columns = axis_totals(
+            self.coordinates.iter().map(|c| c.0),
+            b.min_x.into(),
+            b.max_x.into(),
+        );
+        let rows = axis_totals(
+            self.coordinates.iter().map(|c| c.1),
+            b.min_y.into(),
+            b.max_y.into(),
+        );
+        let safe = |x: i32, y: i32| {
+            (b.min_x..=b.max_x).contains(&x)
+                && (b.min_y..=b.max_y).contains(&y)
+                && columns[(x - b.min_x) as usize] + rows[(y - b.min_y) as usize] < boundary
+        };
+        let areas = self.areas();
+
+        b.cells()
+            .map(|(x, y)| {
+                let outline = safe(x, y)
+                    && !(safe(x - 1, y) && safe(x + 1, y) && safe(x, y - 1) && safe(x, y + 1));
+
+                match self.owner((x, y)) {
+                    Owner::Coordinate(i) if self.coordinates[i] == (x, y) => COORDINATE,
+                    _ if outline => OUTLINE,
+                    Owner::Coordinate(i) if areas[i].is_none() => infinite_colour(i),
+                    Owner::Coordinate(i) => area_colour(i),
+                    Owner::Tie | Owner::Unreached => TIE,
+                }
+            })
+            .collect()
+    }
+
+    /// Rows of RGB bytes of the map of areas, every cell scaled up to
+    /// `scale` x `scale` pixels.
+    fn image(&self, boundary: u64, scale: usize) -> (usize, usize, Vec<Vec<u8>>) {
+        let pixels = self.pixels(boundary);
+        let cells = self.bounds.width();
+        let (width, height) = (cells * scale, self.bounds.height() * scale);
+
+        let rows = (0..height)
+            .map(|y| {
+                (0..width)
+                    .flat_map(|x| pixels[y / scale * cells + x / scale])
+                    .collect()
+            })
+            .collect();
+
+        (width, height, rows)
+    }
+
+    fn write_ppm<W: Write>(&self, boundary: u64, scale: usize, out: &mut W) -> io::Result<()> {
+        let (width, height, rows) = self.image(boundary, scale);
+
+        write!(out, "P6\n{} {}\n255\n", width, height)?;
+        for row in rows {
+            out.write_all(&row)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_png<W: Write>(&self, boundary: u64, scale: usize, out: &mut W) -> io::Result<()> {
+        let (width, height, rows) = self.image(boundary, scale);
+
+        // Every scanline prefixed with filter type 0.
+        let mut raw = Vec::with_capacity((3 * width + 1) * height);
+        for row in rows {
+            raw.push(0);
+            raw.extend(row);
+        }
+
+        png::write(out, width, height, 2, &raw)
+    }
+}
+
+fn run(voronoi: &Voronoi, boundary: u64) -> Result<(usize, u64), String> {
+    let largest = voronoi.areas().into_iter().flatten().max().unwrap_or(0);
+
+    Ok((largest, region_size(&voronoi.coordinates, boundary)?))
+}
+
+/// Command line flags. `--margin N` grows the box the areas are worked out
+/// in by N cells on every side, and `--boundary N` sets the total distance
+/// the region in part 2 has to stay below. `--image FILE` writes a map of
+/// the areas, as PNG or PPM by the file extension, with every cell `--scale`
+/// pixels wide.
+#[derive(Debug, PartialEq)]
+struct Options {
+    margin: i32,
+    boundary: u64,
+    image: Option<PathBuf>,
+    scale: usize,
+}
 
-            if sum < boundary {
-                area += 1;
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            margin: 0,
+            boundary: 10000,
+            image: None,
+            scale: 1,
+        }
+    }
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            fn number<T: FromStr>(value: String) -> Result<T, String> {
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid number {}", value))
+            }
+
+            match arg.as_str() {
+                "--margin" => options.margin = number(value()?)?,
+                "--boundary" => options.boundary = number(value()?)?,
+                "--image" => options.image = Some(PathBuf::from(value()?)),
+                "--scale" => options.scale = number(value()?)?,
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        if options.margin < 0 {
+            return Err(format!("Negative margin {}", options.margin));
+        }
+        if options.scale == 0 {
+            return Err("Scale must be at least 1".to_string());
+        }
+        if let Some(path) = &options.image {
+            if image_format(path).is_none() {
+                return Err(format!("{} is not a .png or .ppm file", path.display()));
             }
         }
+
+        Ok(options)
     }
+}
+
+/// Extension of a supported image file, in lower case.
+fn image_format(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
 
-    (*largest, area)
+    match extension.as_str() {
+        "png" | "ppm" => Some(extension),
+        _ => None,
+    }
 }
 
 fn main() {
+    let fail = |e: String| -> ! {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    };
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| fail(e));
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let (largest, area) = run(&input, 10000);
+    let coordinates = parse_coordinates(&input).unwrap_or_else(|e| fail(e));
+    let voronoi = Voronoi::new(&coordinates, options.margin);
+
+    let (largest, area) = run(&voronoi, options.boundary).unwrap_or_else(|e| fail(e));
     println!("Part 1. Largest area: {}", largest);
     println!("Part 2. Largest area: {}", area);
+
+    if let Some(path) = &options.image {
+        let (boundary, scale) = (options.boundary, options.scale);
+
+        File::create(path)
+            .map(BufWriter::new)
+            .and_then(|mut file| match image_format(path).as_deref() {
+                Some("ppm") => voronoi.write_ppm(boundary, scale, &mut file),
+                _ => voronoi.write_png(boundary, scale, &mut file),
+            })
+            .unwrap_or_else(|e| fail(format!("Failed to write {}: {}", path.display(), e)));
+        println!("Wrote {}", path.display());
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn run(input: &str, boundary: u64, margin: i32) -> (usize, u64) {
+        let coordinates = parse_coordinates(input).unwrap();
+        super::run(&Voronoi::new(&coordinates, margin), boundary).unwrap()
+    }
+
+    fn manhattan_distance((x1, y1): Coordinate, (x2, y2): Coordinate) -> u32 {
+        ((x2 - x1).abs() + (y2 - y1).abs()) as u32
+    }
+
     fn data() -> String {
         String::from(
             "1, 1
@@ -158,13 +491,230 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        let (largest, _) = run(&data(), 32);
+        let (largest, _) = run(&data(), 32, 0);
         assert_eq!(17, largest);
     }
 
     #[test]
     fn test_part2() {
-        let (_, area) = run(&data(), 32);
+        let (_, area) = run(&data(), 32, 0);
         assert_eq!(16, area);
     }
+
+    #[test]
+    fn region_beyond_box() {
+        // Everything within two steps of a single coordinate.
+        assert_eq!(Ok(13), region_size(&[(0, 0)], 3));
+        assert_eq!(Ok(0), region_size(&[(0, 0)], 0));
+        assert_eq!(Ok(1), region_size(&[(-5, 7)], 1));
+
+        // |x| + |y| < n takes 2n^2 - 2n + 1 cells.
+        assert_eq!(Ok(19_999_800_001), region_size(&[(0, 0)], 100_000));
+        assert!(region_size(&[(0, 0)], u64::MAX).is_err());
+        assert!(region_size(&[(0, 0)], i64::MAX as u64 + 1).is_err());
+        assert!(region_size(&[(i32::MIN, 0), (i32::MAX, 0)], 10).is_err());
+
+        // Large enough to spill well outside the box around the example.
+        let coordinates = parse_coordinates(&data()).unwrap();
+        for boundary in [0, 1, 30, 32, 60, 100, 151] {
+            let mut expected = 0;
+            for x in -30..40 {
+                for y in -30..40 {
+                    let sum: u64 = coordinates
+                        .iter()
+                        .map(|&c| u64::from(manhattan_distance(c, (x, y))))
+                        .sum();
+                    if sum < boundary {
+                        expected += 1;
+                    }
+                }
+            }
+
+            assert_eq!(
+                Ok(expected),
+                region_size(&coordinates, boundary),
+                "{}",
+                boundary
+            );
+        }
+    }
+
+    #[test]
+    fn ppm() {
+        let coordinates = parse_coordinates(&data()).unwrap();
+        let voronoi = Voronoi::new(&coordinates, 1);
+        let mut out = Vec::new();
+        voronoi.write_ppm(32, 2, &mut out).unwrap();
+
+        let header = b"P6\n20 22\n255\n";
+        assert_eq!(header, &out[..header.len()]);
+        assert_eq!(header.len() + 20 * 22 * 3, out.len());
+
+        let pixel = |(x, y): Coordinate| {
+            let at = header.len() + 3 * (2 * y as usize * 20 + 2 * x as usize);
+            &out[at..at + 3]
+        };
+        let index = |c| coordinates.iter().position(|&x| x == c).unwrap();
+
+        assert_eq!(&COORDINATE, pixel((1, 1)));
+        // Closest to both (1, 1) and (3, 4).
+        assert_eq!(&TIE, pixel((1, 4)));
+        assert_eq!(&infinite_colour(index((1, 1))), pixel((0, 0)));
+        assert_eq!(&area_colour(index((5, 5))), pixel((5, 2)));
+        // The safe region runs from x = 3 to x = 5 along y = 3, around
+        // (4, 4) inside it.
+        assert_eq!(&area_colour(index((3, 4))), pixel((2, 3)));
+        assert_eq!(&OUTLINE, pixel((3, 3)));
+        assert_eq!(&OUTLINE, pixel((5, 3)));
+        assert_eq!(&area_colour(index((3, 4))), pixel((4, 4)));
+    }
+
+    #[test]
+    fn png() {
+        let coordinates = parse_coordinates(&data()).unwrap();
+        let mut out = Vec::new();
+        Voronoi::new(&coordinates, 0)
+            .write_png(32, 1, &mut out)
+            .unwrap();
+
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &out[..8]);
+        assert_eq!(b"IHDR", &out[12..16]);
+        assert_eq!(&8u32.to_be_bytes(), &out[16..20]);
+        assert_eq!(&9u32.to_be_bytes(), &out[20..24]);
+        // 8 bits per channel, RGB.
+        assert_eq!(&[8, 2], &out[24..26]);
+        assert_eq!(b"IEND", &out[out.len() - 8..out.len() - 4]);
+    }
+
+    #[test]
+    fn colours() {
+        // Neighbouring coordinates get clearly different colours.
+        for i in 0..50 {
+            let (a, b) = (area_colour(i), area_colour(i + 1));
+            let difference: u32 = a
+                .iter()
+                .zip(&b)
+                .map(|(&x, &y)| u32::from(x.abs_diff(y)))
+                .sum();
+            assert!(difference > 60, "{} {:?} {:?}", i, a, b);
+            assert_ne!(TIE, a);
+        }
+    }
+
+    /// Closest coordinate of every cell by comparing with all coordinates.
+    fn brute_force(coordinates: &[Coordinate], cell: Coordinate) -> Owner {
+        let distances: Vec<u32> = coordinates
+            .iter()
+            .map(|&c| manhattan_distance(c, cell))
+            .collect();
+        let min = *distances.iter().min().unwrap();
+
+        match distances.iter().filter(|&&d| d == min).count() {
+            1 => Owner::Coordinate(distances.iter().position(|&d| d == min).unwrap()),
+            _ => Owner::Tie,
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let coordinates = parse_coordinates(&data()).unwrap();
+        let voronoi = Voronoi::new(&coordinates, 3);
+
+        for cell in voronoi.bounds.cells() {
+            assert_eq!(
+                brute_force(&coordinates, cell),
+                voronoi.owner(cell),
+                "{:?}",
+                cell
+            );
+        }
+    }
+
+    #[test]
+    fn areas() {
+        let coordinates = parse_coordinates(&data()).unwrap();
+        let index = |c| coordinates.iter().position(|&x| x == c).unwrap();
+
+        for margin in 0..4 {
+            let areas = Voronoi::new(&coordinates, margin).areas();
+
+            assert_eq!(Some(9), areas[index((3, 4))]);
+            assert_eq!(Some(17), areas[index((5, 5))]);
+            assert_eq!(None, areas[index((1, 1))]);
+            assert_eq!(4, areas.iter().filter(|a| a.is_none()).count());
+        }
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        // The example moved into negative coordinates.
+        let moved: String = parse_coordinates(&data())
+            .unwrap()
+            .iter()
+            .map(|(x, y)| format!("{}, {}\n", x - 10, y - 20))
+            .collect();
+
+        assert_eq!(run(&data(), 32, 0), run(&moved, 32, 0));
+        assert_eq!(run(&data(), 32, 0), run(&moved, 32, 2));
+    }
+
+    #[test]
+    fn ties() {
+        // Two coordinates split the plane down the middle column, which is
+        // closest to both.
+        let coordinates = parse_coordinates("0, 0\n4, 0").unwrap();
+        let voronoi = Voronoi::new(&coordinates, 2);
+
+        assert_eq!(Owner::Tie, voronoi.owner((2, 0)));
+        assert_eq!(Owner::Tie, voronoi.owner((2, -2)));
+        assert_eq!(Owner::Coordinate(0), voronoi.owner((1, 2)));
+        assert_eq!(Owner::Coordinate(1), voronoi.owner((3, -1)));
+        assert_eq!(vec![None, None], voronoi.areas());
+
+        // A coordinate boxed in on all sides has a finite area.
+        let coordinates = parse_coordinates("0, 0\n4, 0\n0, 4\n4, 4\n2, 2").unwrap();
+        let areas = Voronoi::new(&coordinates, 1).areas();
+        assert_eq!(Some(5), areas[2]);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Ok(vec![(-3, 4), (1, -2)]),
+            parse_coordinates("1, -2\n\n-3,4\n1, -2")
+        );
+        assert!(parse_coordinates("").is_err());
+        assert!(parse_coordinates("1").is_err());
+        assert!(parse_coordinates("1, 2, 3").is_err());
+        assert!(parse_coordinates("1, x").is_err());
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        assert_eq!(
+            3,
+            Options::from_args(args("--margin 3").into_iter())
+                .unwrap()
+                .margin
+        );
+        assert_eq!(
+            Options {
+                boundary: 50,
+                ..Options::default()
+            },
+            Options::from_args(args("--boundary 50").into_iter()).unwrap()
+        );
+        assert!(Options::from_args(args("--boundary -1").into_iter()).is_err());
+        assert!(Options::from_args(args("--margin -1").into_iter()).is_err());
+
+        let options = Options::from_args(args("--image map.PPM --scale 4").into_iter()).unwrap();
+        assert_eq!(Some(PathBuf::from("map.PPM")), options.image);
+        assert_eq!(4, options.scale);
+        assert!(Options::from_args(args("--image map.gif").into_iter()).is_err());
+        assert!(Options::from_args(args("--image map").into_iter()).is_err());
+        assert!(Options::from_args(args("--scale 0").into_iter()).is_err());
+        assert!(Options::from_args(args("--margin").into_iter()).is_err());
+    }
 }
@@ -1,171 +1,924 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{btree_set, BTreeSet, BinaryHeap, HashMap};
+use std::fmt;
 use std::io::{stdin, Read};
 use std::str::FromStr;
 
-type Step = u8;
+type Step = String;
 
+/// Which of the available steps an idle worker picks first. Ties are always
+/// broken alphabetically.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Priority {
+    Alphabetical,
+    LongestFirst,
+    /// The step most other steps are directly waiting for.
+    MostDependents,
+}
+
+impl FromStr for Priority {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alphabetical" => Ok(Priority::Alphabetical),
+            "longest" => Ok(Priority::LongestFirst),
+            "dependents" => Ok(Priority::MostDependents),
+            _ => Err(format!(
+                "Unknown priority {}, expected alphabetical, longest or dependents",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 struct Config {
     num_workers: usize,
-    alpha_zero: u8,
+    /// Seconds every lettered step takes on top of its place in the alphabet.
+    base_duration: u32,
+    /// Durations read from a file, overriding those of lettered steps.
+    durations: HashMap<Step, u32>,
+    priority: Priority,
 }
 
 impl Config {
     fn part1() -> Self {
         Config {
             num_workers: 1,
-            alpha_zero: b'A', // doesn't matter
+            base_duration: 0, // doesn't matter
+            durations: HashMap::new(),
+            priority: Priority::Alphabetical,
         }
     }
 
     fn part2() -> Self {
         Config {
             num_workers: 5,
-            alpha_zero: b'A' - 60,
+            base_duration: 60,
+            durations: HashMap::new(),
+            priority: Priority::Alphabetical,
+        }
+    }
+
+    /// Seconds it takes to do `step`. Steps named by a single capital letter
+    /// take the base duration plus 1 for A up to 26 for Z, all others must
+    /// be given a duration.
+    fn duration(&self, step: &str) -> Result<u32, String> {
+        if let Some(&seconds) = self.durations.get(step) {
+            return Ok(seconds);
+        }
+
+        match step.as_bytes() {
+            &[letter] if letter.is_ascii_uppercase() => {
+                Ok(self.base_duration + u32::from(letter - b'A') + 1)
+            }
+            _ => Err(format!("No duration for step {}", step)),
         }
     }
 }
 
+/// Reads `NAME SECONDS` lines, skipping blank ones.
+fn parse_durations(input: &str) -> Result<HashMap<Step, u32>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(
+            |(i, line)| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [step, seconds] => seconds
+                    .parse()
+                    .map(|seconds| (step.to_string(), seconds))
+                    .map_err(|_| format!("Line {}: Invalid duration {}", i + 1, seconds)),
+                _ => Err(format!("Line {}: Expected NAME SECONDS", i + 1)),
+            },
+        )
+        .collect()
+}
+
 struct Instruction {
     name: Step,
     blocks: Step,
 }
 
 impl FromStr for Instruction {
-    type Err = ();
+    type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut iter = s.split_whitespace();
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["Step", name, "must", "be", "finished", "before", "step", blocks, "can", "begin."] => {
+                Ok(Instruction {
+                    name: name.to_string(),
+                    blocks: blocks.to_string(),
+                })
+            }
+            _ => Err(format!("Invalid instruction '{}'", s.trim())),
+        }
+    }
+}
 
-        let name = iter.nth(1).unwrap().as_bytes()[0];
-        let blocks = iter.nth(5).unwrap().as_bytes()[0];
+fn parse_dependencies(input: &str) -> Result<HashMap<Step, BTreeSet<Step>>, String> {
+    let mut dependencies = HashMap::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Instruction { name, blocks } =
+            line.parse().map_err(|e| format!("Line {}: {}", i + 1, e))?;
 
-        Ok(Instruction { name, blocks })
+        dependencies
+            .entry(name.clone())
+            .or_insert_with(BTreeSet::new);
+        dependencies
+            .entry(blocks)
+            .or_insert_with(BTreeSet::new)
+            .insert(name);
     }
+
+    Ok(dependencies)
 }
 
-#[derive(Clone, Debug)]
-enum WorkerState {
-    Working,
-    Done { step: Step },
+/// Every step after the steps it depends on, found depth first from the
+/// steps in alphabetical order, or an error naming a cycle if there is none.
+/// The search keeps its own stack, so long chains of steps cannot overflow
+/// the call stack.
+fn topological_order(dependencies: &HashMap<Step, BTreeSet<Step>>) -> Result<Vec<&str>, String> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Visiting,
+        Done,
+    }
+
+    let steps: BTreeSet<&str> = dependencies.keys().map(String::as_str).collect();
+    let mut marks = HashMap::new();
+    let mut order = Vec::with_capacity(steps.len());
+
+    // Steps being visited, each with the steps it depends on that are left
+    // to visit. From the bottom up it is a path from dependents to what they
+    // depend on.
+    let mut path: Vec<(&str, btree_set::Iter<Step>)> = Vec::new();
+
+    for step in steps {
+        if marks.contains_key(step) {
+            continue;
+        }
+
+        marks.insert(step, Mark::Visiting);
+        path.push((step, dependencies[step].iter()));
+
+        while let Some((step, befores)) = path.last_mut() {
+            let step = *step;
+
+            match befores.next().map(String::as_str) {
+                Some(before) => match marks.get(before) {
+                    Some(Mark::Done) => {}
+                    Some(Mark::Visiting) => {
+                        // The cycle reads backwards along the path from
+                        // where it closes.
+                        let start = path.iter().position(|&(s, _)| s == before).unwrap();
+                        let mut cycle: Vec<&str> =
+                            path[start..].iter().rev().map(|p| p.0).collect();
+                        let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+                        cycle.rotate_left(first);
+                        cycle.push(cycle[0]);
+
+                        return Err(format!(
+                            "Steps depend on each other: {}",
+                            cycle.join(" -> ")
+                        ));
+                    }
+                    None => {
+                        marks.insert(before, Mark::Visiting);
+                        path.push((before, dependencies[before].iter()));
+                    }
+                },
+                None => {
+                    marks.insert(step, Mark::Done);
+                    order.push(step);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    Ok(order)
 }
 
-fn parse_dependencies(input: &str) -> HashMap<Step, BTreeSet<Step>> {
-    let mut dependencies = HashMap::new();
-    input
-        .lines()
-        .flat_map(Instruction::from_str)
-        .for_each(|Instruction { name, blocks }| {
-            dependencies.entry(name).or_insert_with(BTreeSet::new);
-            dependencies
-                .entry(blocks)
-                .or_insert_with(BTreeSet::new)
-                .insert(name);
-        });
-
-    dependencies
-}
-
-fn step_sequence(
-    Config {
-        num_workers,
-        alpha_zero,
-    }: Config,
+/// A step done by a worker from `start` until `end`.
+#[derive(Clone, Debug, PartialEq)]
+struct Task {
+    step: Step,
+    worker: usize,
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug)]
+struct Schedule {
+    /// Every step, in the order they were started.
+    tasks: Vec<Task>,
+    num_workers: usize,
+}
+
+impl Schedule {
+    /// Steps in the order they were started, run together when every name
+    /// is a single character and separated by spaces otherwise.
+    fn order(&self) -> String {
+        let steps: Vec<&str> = self.tasks.iter().map(|t| t.step.as_str()).collect();
+
+        match steps.iter().all(|s| s.chars().count() == 1) {
+            true => steps.concat(),
+            false => steps.join(" "),
+        }
+    }
+
+    /// Seconds until the last step is done.
+    fn seconds(&self) -> u32 {
+        self.tasks.iter().map(|t| t.end).max().unwrap_or(0)
+    }
+
+    /// One row per worker, `width` columns wide, showing the first character
+    /// of the step being worked on, followed by the tasks of every worker.
+    fn gantt(&self, width: usize) -> String {
+        let seconds = self.seconds().max(1) as usize;
+        let scale = seconds.div_ceil(width.max(1));
+        let mut chart = format!("{} seconds, {} per column\n", seconds, scale);
+
+        for worker in 0..self.num_workers {
+            let tasks: Vec<&Task> = self.tasks.iter().filter(|t| t.worker == worker).collect();
+
+            let row: String = (0..seconds.div_ceil(scale))
+                .map(|column| {
+                    let time = (column * scale) as u32;
+                    tasks
+                        .iter()
+                        .find(|t| t.start <= time && time < t.end)
+                        .map_or('.', |t| t.step.chars().next().unwrap())
+                })
+                .collect();
+
+            let list: Vec<String> = tasks
+                .iter()
+                .map(|t| format!("{} {}-{}", t.step, t.start, t.end))
+                .collect();
+
+            chart += &format!("Worker {:2} |{}| {}\n", worker + 1, row, list.join(", "));
+        }
+
+        chart
+    }
+}
+
+/// Assigns steps to workers, jumping from one completion to the next rather
+/// than ticking through every second. Whenever workers are idle they take
+/// the available steps in order of priority, lowest numbered worker first.
+fn schedule(
+    config: &Config,
     dependencies: &HashMap<Step, BTreeSet<Step>>,
-) -> (String, u32) {
-    let steps: BTreeSet<Step> = dependencies.keys().copied().collect();
-
-    let mut visited: HashSet<Step> = HashSet::with_capacity(steps.len());
-    let mut worked_on: HashSet<Step> = HashSet::with_capacity(num_workers);
-    let mut visits = Vec::with_capacity(steps.len());
-
-    // Work queue.
-    let mut workers: Vec<Vec<WorkerState>> = vec![Vec::new(); num_workers];
-
-    let mut seconds = 0;
-    while steps.len() != visited.len() {
-        // First clear off all work.
-        workers.iter_mut().for_each(|worker| match worker.pop() {
-            Some(WorkerState::Working) | None => (),
-            Some(WorkerState::Done { step }) => {
-                visited.insert(step);
-                worked_on.remove(&step);
+) -> Result<Schedule, String> {
+    topological_order(dependencies)?;
+
+    let mut blocked_by: HashMap<&str, usize> = HashMap::new();
+    let mut blocks: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for (step, before) in dependencies {
+        blocked_by.insert(step, before.len());
+        for b in before {
+            blocks.entry(b).or_default().push(step);
+        }
+    }
+
+    // Smallest first, so larger is better for the other priorities.
+    let rank = |step: &str| -> Result<Reverse<u32>, String> {
+        Ok(Reverse(match config.priority {
+            Priority::Alphabetical => 0,
+            Priority::LongestFirst => config.duration(step)?,
+            Priority::MostDependents => blocks.get(step).map_or(0, Vec::len) as u32,
+        }))
+    };
+
+    let mut available: BTreeSet<(Reverse<u32>, &str)> = dependencies
+        .iter()
+        .filter(|(_, before)| before.is_empty())
+        .map(|(step, _)| Ok((rank(step)?, step.as_str())))
+        .collect::<Result<_, String>>()?;
+    let mut idle: BTreeSet<usize> = (0..config.num_workers).collect();
+
+    // Steps being worked on, ordered by when they are done.
+    let mut working = BinaryHeap::new();
+    let mut tasks = Vec::with_capacity(dependencies.len());
+    let mut time = 0;
+
+    loop {
+        while !idle.is_empty() && !available.is_empty() {
+            let (_, step) = available.pop_first().unwrap();
+            let worker = idle.pop_first().unwrap();
+
+            let end = time + config.duration(step)?;
+            working.push(Reverse((end, worker, tasks.len())));
+            tasks.push(Task {
+                step: step.to_string(),
+                worker,
+                start: time,
+                end,
+            });
+        }
+
+        let Reverse((end, ..)) = match working.peek() {
+            Some(&next) => next,
+            None => break,
+        };
+        time = end;
+
+        // Finish everything done by now before anyone picks new work, or idle
+        // workers would miss steps released by a later worker.
+        while let Some(&Reverse((end, worker, task))) = working.peek() {
+            if end > time {
+                break;
             }
-        });
-
-        // Then reiterate to add work to everyone idle.
-        // Needs to be done in a separate loop,
-        // or idle workers in the front will miss available steps released after them.
-        workers
-            .iter_mut()
-            .filter(|worker_queue| worker_queue.is_empty())
-            .for_each(|worker| {
-                let visited_or_processing = |s| visited.contains(s) || worked_on.contains(s);
-                let dependencies_cleared = |s| dependencies[s].iter().all(|i| visited.contains(i));
-
-                if let Some(&step) = steps
-                    .iter()
-                    .find(|s| !visited_or_processing(s) && dependencies_cleared(s))
-                {
-                    let wait = step - alpha_zero;
-
-                    worker.push(WorkerState::Done { step });
-                    (0..wait).for_each(|_| worker.push(WorkerState::Working));
-
-                    worked_on.insert(step);
-                    visits.push(step);
+            working.pop();
+            idle.insert(worker);
+
+            for &next in blocks.get(tasks[task].step.as_str()).into_iter().flatten() {
+                let count = blocked_by.get_mut(next).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    available.insert((rank(next)?, next));
                 }
+            }
+        }
+    }
+
+    Ok(Schedule {
+        tasks,
+        num_workers: config.num_workers,
+    })
+}
+
+/// The fewest workers that finish within `budget` seconds, if any number
+/// does. More workers don't always finish sooner when they are handed steps
+/// greedily, so every count is tried in turn.
+fn min_workers(
+    config: &Config,
+    dependencies: &HashMap<Step, BTreeSet<Step>>,
+    budget: u32,
+) -> Result<Option<usize>, String> {
+    // With a worker for every step, nothing ever waits for a worker.
+    if Analysis::new(config, dependencies)?.length > budget {
+        return Ok(None);
+    }
+
+    for num_workers in 1..=dependencies.len() {
+        let config = Config {
+            num_workers,
+            ..config.clone()
+        };
+
+        if schedule(&config, dependencies)?.seconds() <= budget {
+            return Ok(Some(num_workers));
+        }
+    }
+
+    Ok(Some(dependencies.len().max(1)))
+}
+
+/// When a step can be done with as many workers as needed.
+#[derive(Debug, PartialEq)]
+struct Timing {
+    step: Step,
+    duration: u32,
+    /// Earliest start, once everything it depends on is done.
+    earliest: u32,
+    /// Latest start that doesn't delay the end of the whole job.
+    latest: u32,
+}
+
+impl Timing {
+    fn slack(&self) -> u32 {
+        self.latest - self.earliest
+    }
+}
+
+/// Critical path analysis of the steps, ignoring the number of workers.
+#[derive(Debug)]
+struct Analysis {
+    /// Timings in topological order.
+    timings: Vec<Timing>,
+    /// Seconds the job takes with enough workers.
+    length: u32,
+    /// A chain of steps without slack from start to end.
+    critical_path: Vec<Step>,
+}
+
+impl Analysis {
+    fn new(config: &Config, dependencies: &HashMap<Step, BTreeSet<Step>>) -> Result<Self, String> {
+        let order = topological_order(dependencies)?;
+        let index: HashMap<&str, usize> = order.iter().enumerate().map(|(i, &s)| (s, i)).collect();
+
+        let mut timings = Vec::with_capacity(order.len());
+        for &step in &order {
+            let earliest = dependencies[step]
+                .iter()
+                .map(|b| {
+                    let before: &Timing = &timings[index[b.as_str()]];
+                    before.earliest + before.duration
+                })
+                .max()
+                .unwrap_or(0);
+
+            timings.push(Timing {
+                step: step.to_string(),
+                duration: config.duration(step)?,
+                earliest,
+                latest: 0,
             });
+        }
+
+        let length = timings
+            .iter()
+            .map(|t| t.earliest + t.duration)
+            .max()
+            .unwrap_or(0);
 
-        seconds += 1;
+        // Backwards, every step must be done before any of its dependents
+        // has to start.
+        let mut finish = vec![length; timings.len()];
+        for i in (0..timings.len()).rev() {
+            let latest = finish[i] - timings[i].duration;
+            timings[i].latest = latest;
+
+            for before in &dependencies[&timings[i].step] {
+                let b = index[before.as_str()];
+                finish[b] = finish[b].min(latest);
+            }
+        }
+
+        let critical = |t: &&Timing| t.slack() == 0;
+        let mut critical_path = Vec::new();
+        let mut next = timings
+            .iter()
+            .filter(critical)
+            .filter(|t| t.earliest == 0)
+            .min_by_key(|t| &t.step);
+
+        while let Some(timing) = next {
+            critical_path.push(timing.step.clone());
+            let end = timing.earliest + timing.duration;
+
+            next = timings
+                .iter()
+                .filter(critical)
+                .filter(|t| t.earliest == end && dependencies[&t.step].contains(&timing.step))
+                .min_by_key(|t| &t.step);
+        }
+
+        Ok(Analysis {
+            timings,
+            length,
+            critical_path,
+        })
     }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Length: {} seconds", self.length)?;
+        writeln!(f, "Critical path: {}", self.critical_path.join(" -> "))?;
+        writeln!(f, "Step     Duration Earliest   Latest    Slack")?;
+
+        for t in &self.timings {
+            writeln!(
+                f,
+                "{:8} {:8} {:8} {:8} {:8}",
+                t.step,
+                t.duration,
+                t.earliest,
+                t.latest,
+                t.slack()
+            )?;
+        }
 
-    (String::from_utf8(visits).unwrap(), seconds - 1)
+        Ok(())
+    }
+}
+
+/// The dependency graph in Graphviz DOT, with an edge from every step to
+/// the steps waiting for it and the critical path in red.
+fn dot(dependencies: &HashMap<Step, BTreeSet<Step>>, analysis: &Analysis) -> String {
+    let critical = |step: &str| analysis.critical_path.iter().any(|s| s == step);
+    let mut dot = String::from("digraph steps {\n    rankdir=LR;\n");
+
+    for t in &analysis.timings {
+        let colour = if critical(&t.step) { ", color=red" } else { "" };
+        dot += &format!(
+            "    \"{}\" [label=\"{}\\n{}s, slack {}\"{}];\n",
+            t.step,
+            t.step,
+            t.duration,
+            t.slack(),
+            colour
+        );
+    }
+
+    for t in &analysis.timings {
+        for before in &dependencies[&t.step] {
+            let on_path = analysis
+                .critical_path
+                .windows(2)
+                .any(|w| w[0] == *before && w[1] == t.step);
+            let colour = if on_path { " [color=red]" } else { "" };
+            dot += &format!("    \"{}\" -> \"{}\"{};\n", before, t.step, colour);
+        }
+    }
+
+    dot + "}\n"
+}
+
+/// Command line flags. `--workers N`, `--base-duration N`, `--priority
+/// alphabetical|longest|dependents` and `--durations FILE` set up part 2,
+/// and `--gantt WIDTH` prints its schedule as a chart. `--analyse` prints the
+/// critical path and slack of every step, `--dot FILE` writes the graph for
+/// Graphviz and `--budget SECONDS` finds the fewest workers that are enough.
+#[derive(Debug, PartialEq)]
+struct Options {
+    config: Config,
+    durations: Option<String>,
+    gantt: Option<usize>,
+    analyse: bool,
+    dot: Option<String>,
+    budget: Option<u32>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            config: Config::part2(),
+            durations: None,
+            gantt: None,
+            analyse: false,
+            dot: None,
+            budget: None,
+        }
+    }
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        fn number<T: FromStr>(value: String) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid number {}", value))
+        }
+
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+
+            match arg.as_str() {
+                "--workers" => options.config.num_workers = number(value()?)?,
+                "--base-duration" => options.config.base_duration = number(value()?)?,
+                "--priority" => options.config.priority = value()?.parse()?,
+                "--durations" => options.durations = Some(value()?),
+                "--gantt" => options.gantt = Some(number(value()?)?),
+                "--analyse" => options.analyse = true,
+                "--dot" => options.dot = Some(value()?),
+                "--budget" => options.budget = Some(number(value()?)?),
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        if options.config.num_workers == 0 {
+            return Err("At least one worker is needed".to_string());
+        }
+
+        Ok(options)
+    }
 }
 
 fn main() {
+    let fail = |e: String| -> ! {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    };
+    let mut options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| fail(e));
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let dependencies = parse_dependencies(&input);
+    let dependencies = parse_dependencies(&input).unwrap_or_else(|e| fail(e));
+
+    if let Some(path) = &options.durations {
+        let durations = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))
+            .and_then(|s| parse_durations(&s))
+            .unwrap_or_else(|e| fail(e));
+        options.config.durations = durations;
+    }
+    let part2 = &options.config;
+
+    // Part 1 only cares about the order, so any duration will do.
+    let mut part1 = Config::part1();
+    part1.durations = dependencies.keys().map(|s| (s.clone(), 1)).collect();
+
+    let visits = schedule(&part1, &dependencies).unwrap_or_else(|e| fail(e));
+    let work = schedule(part2, &dependencies).unwrap_or_else(|e| fail(e));
+
+    println!("Part 1. Step order: {}", visits.order());
+    println!("Part 2. Seconds: {}", work.seconds());
+
+    if let Some(width) = options.gantt {
+        print!("{}", work.gantt(width));
+    }
+
+    if options.analyse || options.dot.is_some() {
+        let analysis = Analysis::new(part2, &dependencies).unwrap_or_else(|e| fail(e));
+
+        if options.analyse {
+            print!("{}", analysis);
+        }
 
-    let (visits, _) = step_sequence(Config::part1(), &dependencies);
-    let (_, seconds) = step_sequence(Config::part2(), &dependencies);
+        if let Some(path) = &options.dot {
+            std::fs::write(path, dot(&dependencies, &analysis))
+                .unwrap_or_else(|e| fail(format!("Failed to write {}: {}", path, e)));
+        }
+    }
 
-    println!("Part 1. Step order: {}", visits);
-    println!("Part 2. Seconds: {}", seconds);
+    if let Some(budget) = options.budget {
+        match min_workers(part2, &dependencies, budget).unwrap_or_else(|e| fail(e)) {
+            Some(workers) => println!("{} workers finish within {} seconds", workers, budget),
+            None => println!("No number of workers finishes within {} seconds", budget),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn dependencies() -> HashMap<Step, BTreeSet<Step>> {
-        parse_dependencies(
-            "Step C must be finished before step A can begin.
+    const EXAMPLE: &str = "Step C must be finished before step A can begin.
 Step C must be finished before step F can begin.
 Step A must be finished before step B can begin.
 Step A must be finished before step D can begin.
 Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
-Step F must be finished before step E can begin.",
-        )
+Step F must be finished before step E can begin.";
+
+    fn dependencies() -> HashMap<Step, BTreeSet<Step>> {
+        parse_dependencies(EXAMPLE).unwrap()
+    }
+
+    fn config(num_workers: usize, base_duration: u32) -> Config {
+        Config {
+            num_workers,
+            base_duration,
+            durations: HashMap::new(),
+            priority: Priority::Alphabetical,
+        }
     }
 
     #[test]
     fn test_part1() {
-        let (visits, _) = step_sequence(Config::part1(), &dependencies());
-        assert_eq!("CABDFE", visits);
+        let schedule = schedule(&Config::part1(), &dependencies()).unwrap();
+        assert_eq!("CABDFE", schedule.order());
     }
 
     #[test]
     fn test_part2() {
-        let config = Config {
-            num_workers: 2,
-            alpha_zero: b'A',
+        let schedule = schedule(&config(2, 0), &dependencies()).unwrap();
+        assert_eq!(15, schedule.seconds());
+        assert_eq!("CAFBDE", schedule.order());
+    }
+
+    #[test]
+    fn tasks() {
+        let schedule = schedule(&config(2, 0), &dependencies()).unwrap();
+        let task = |step: &str, worker, start, end| Task {
+            step: step.to_string(),
+            worker,
+            start,
+            end,
+        };
+
+        // From the worked example in the puzzle.
+        assert_eq!(
+            vec![
+                task("C", 0, 0, 3),
+                task("A", 0, 3, 4),
+                task("F", 1, 3, 9),
+                task("B", 0, 4, 6),
+                task("D", 0, 6, 10),
+                task("E", 0, 10, 15),
+            ],
+            schedule.tasks
+        );
+    }
+
+    #[test]
+    fn gantt() {
+        let schedule = schedule(&config(2, 0), &dependencies()).unwrap();
+
+        assert_eq!(
+            "15 seconds, 1 per column
+Worker  1 |CCCABBDDDDEEEEE| C 0-3, A 3-4, B 4-6, D 6-10, E 10-15
+Worker  2 |...FFFFFF......| F 3-9
+",
+            schedule.gantt(20)
+        );
+        assert!(schedule
+            .gantt(5)
+            .starts_with("15 seconds, 3 per column\nWorker  1 |CADDE|"));
+    }
+
+    #[test]
+    fn named_steps() {
+        let input = "Step fetch must be finished before step build can begin.
+Step build must be finished before step test can begin.
+Step build must be finished before step lint can begin.
+Step lint must be finished before step ship can begin.
+Step test must be finished before step ship can begin.";
+        let dependencies = parse_dependencies(input).unwrap();
+
+        let mut config = config(2, 0);
+        assert!(schedule(&config, &dependencies).is_err());
+
+        config.durations =
+            parse_durations("fetch 10\nbuild 100\n\ntest 50\nlint 5\nship 1\n").unwrap();
+        let schedule = schedule(&config, &dependencies).unwrap();
+
+        assert_eq!("fetch build lint test ship", schedule.order());
+        assert_eq!(161, schedule.seconds());
+    }
+
+    #[test]
+    fn durations() {
+        let mut config = config(1, 60);
+        assert_eq!(Ok(61), config.duration("A"));
+        assert_eq!(Ok(86), config.duration("Z"));
+        assert!(config.duration("a").is_err());
+
+        config.durations = parse_durations("A 5").unwrap();
+        assert_eq!(Ok(5), config.duration("A"));
+
+        assert!(parse_durations("A").is_err());
+        assert!(parse_durations("A five").is_err());
+    }
+
+    #[test]
+    fn invalid_instruction() {
+        assert!(
+            parse_dependencies("Step C must be done before step A can begin.")
+                .unwrap_err()
+                .starts_with("Line 1")
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let input = "Step A must be finished before step B can begin.
+Step B must be finished before step C can begin.
+Step C must be finished before step D can begin.
+Step D must be finished before step B can begin.";
+        let dependencies = parse_dependencies(input).unwrap();
+
+        assert_eq!(
+            "Steps depend on each other: B -> C -> D -> B",
+            topological_order(&dependencies).unwrap_err()
+        );
+        assert!(schedule(&config(2, 0), &dependencies).is_err());
+        assert!(Analysis::new(&config(2, 0), &dependencies).is_err());
+
+        // A cycle at the end of a long chain.
+        let mut dependencies: HashMap<Step, BTreeSet<Step>> = (0..100_000)
+            .map(|i| {
+                (
+                    format!("S{}", i),
+                    vec![format!("S{}", i + 1)].into_iter().collect(),
+                )
+            })
+            .collect();
+        dependencies.insert("S100000".to_string(), BTreeSet::new());
+        let order = topological_order(&dependencies).unwrap();
+        assert_eq!((100_001, "S100000"), (order.len(), order[0]));
+
+        dependencies.insert(
+            "S100000".to_string(),
+            vec!["S99999".to_string()].into_iter().collect(),
+        );
+        assert_eq!(
+            "Steps depend on each other: S100000 -> S99999 -> S100000",
+            topological_order(&dependencies).unwrap_err()
+        );
+
+        let input = "Step A must be finished before step A can begin.";
+        assert_eq!(
+            "Steps depend on each other: A -> A",
+            topological_order(&parse_dependencies(input).unwrap()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn analysis() {
+        let analysis = Analysis::new(&config(2, 0), &dependencies()).unwrap();
+        let timing = |step: &str| {
+            let t = analysis.timings.iter().find(|t| t.step == step).unwrap();
+            (t.earliest, t.latest, t.slack())
         };
 
-        let (_, seconds) = step_sequence(config, &dependencies());
-        assert_eq!(15, seconds);
+        // C 3, A 1, F 6, B 2, D 4, E 5: C -> F -> E is the longest chain.
+        assert_eq!(14, analysis.length);
+        assert_eq!(vec!["C", "F", "E"], analysis.critical_path);
+        assert_eq!((0, 0, 0), timing("C"));
+        assert_eq!((3, 4, 1), timing("A"));
+        assert_eq!((4, 7, 3), timing("B"));
+        assert_eq!((4, 5, 1), timing("D"));
+        assert_eq!((3, 3, 0), timing("F"));
+        assert_eq!((9, 9, 0), timing("E"));
+
+        let text = analysis.to_string();
+        assert!(text.starts_with("Length: 14 seconds\nCritical path: C -> F -> E\n"));
+        assert!(text.contains("\nB               2        4        7        3\n"));
+    }
+
+    #[test]
+    fn dot_export() {
+        let dependencies = dependencies();
+        let analysis = Analysis::new(&config(2, 0), &dependencies).unwrap();
+        let dot = dot(&dependencies, &analysis);
+
+        assert!(dot.starts_with("digraph steps {\n"));
+        assert!(dot.contains("    \"F\" [label=\"F\\n6s, slack 0\", color=red];\n"));
+        assert!(dot.contains("    \"B\" [label=\"B\\n2s, slack 3\"];\n"));
+        assert!(dot.contains("    \"C\" -> \"F\" [color=red];\n"));
+        assert!(dot.contains("    \"C\" -> \"A\";\n"));
+        assert_eq!(7, dot.matches("->").count());
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn priorities() {
+        let mut config = config(2, 0);
+
+        // F, the longest step, goes before A, but then B waits until D is
+        // done and E starts no sooner.
+        config.priority = Priority::LongestFirst;
+        let longest = schedule(&config, &dependencies()).unwrap();
+        assert_eq!("CFADBE", longest.order());
+        assert_eq!(15, longest.seconds());
+
+        // A blocks both B and D while F only blocks E.
+        config.priority = Priority::MostDependents;
+        assert_eq!(
+            "CAFBDE",
+            schedule(&config, &dependencies()).unwrap().order()
+        );
+
+        assert_eq!(Ok(Priority::LongestFirst), "longest".parse());
+        assert!("shortest".parse::<Priority>().is_err());
+    }
+
+    #[test]
+    fn minimum_workers() {
+        let config = config(5, 0);
+
+        // 21 seconds of work in total, at least 14 however many workers.
+        assert_eq!(Ok(Some(1)), min_workers(&config, &dependencies(), 21));
+        assert_eq!(Ok(Some(2)), min_workers(&config, &dependencies(), 20));
+        assert_eq!(Ok(Some(2)), min_workers(&config, &dependencies(), 15));
+        assert_eq!(Ok(Some(3)), min_workers(&config, &dependencies(), 14));
+        assert_eq!(Ok(None), min_workers(&config, &dependencies(), 13));
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let options =
+            Options::from_args(args("--durations times.txt --gantt 80").into_iter()).unwrap();
+        assert_eq!(Some("times.txt".to_string()), options.durations);
+        assert_eq!(Some(80), options.gantt);
+        assert!(!options.analyse);
+
+        let options = Options::from_args(args("--analyse --dot graph.dot").into_iter()).unwrap();
+        assert!(options.analyse);
+        assert_eq!(Some("graph.dot".to_string()), options.dot);
+        assert_eq!(Config::part2(), options.config);
+
+        let options = Options::from_args(
+            args("--workers 2 --base-duration 0 --priority dependents --budget 30").into_iter(),
+        )
+        .unwrap();
+        assert_eq!(2, options.config.num_workers);
+        assert_eq!(0, options.config.base_duration);
+        assert_eq!(Priority::MostDependents, options.config.priority);
+        assert_eq!(Some(30), options.budget);
+
+        assert!(Options::from_args(args("--workers 0").into_iter()).is_err());
+        assert!(Options::from_args(args("--priority random").into_iter()).is_err());
+
+        assert!(Options::from_args(args("--gantt").into_iter()).is_err());
+        assert!(Options::from_args(args("--gantt wide").into_iter()).is_err());
     }
 }
@@ -1,75 +1,660 @@
+use std::fmt;
 use std::io::{stdin, Read};
+use std::str::FromStr;
 
+#[derive(Debug)]
 struct Node {
     children: Vec<Node>,
     metadata: Vec<u32>,
 }
 
+/// Whether `Node::walk` is entering a node or leaving it after all its
+/// children.
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    Enter,
+    Leave,
+}
+
+// Nothing below recurses, so that licences nested arbitrarily deep can't
+// overflow the stack. That includes dropping and comparing trees.
 impl Node {
     fn metadata_sum(&self) -> u32 {
-        self.metadata
-            .iter()
-            .copied()
-            .chain(self.children.iter().map(Node::metadata_sum))
-            .sum()
+        let mut sum = 0;
+        self.walk(&mut Vec::new(), &mut |visit, _, node| {
+            if visit == Visit::Enter {
+                sum += node.metadata.iter().sum::<u32>();
+            }
+        });
+
+        sum
     }
 
     fn value(&self) -> u32 {
-        let meta = self.metadata.iter();
-        if self.children.is_empty() {
-            meta.sum()
-        } else {
-            meta.flat_map(|index| self.children.get((index - 1) as usize))
-                .map(Node::value)
-                .sum()
+        self.values()[0]
+    }
+
+    /// Value of every node in this subtree, in the order they are entered.
+    /// Children are worked out before their parents, so the values of the
+    /// latest finished subtrees are kept on a stack until the parent needs
+    /// them.
+    fn values(&self) -> Vec<u32> {
+        let mut values = Vec::new();
+        let mut entered = Vec::new();
+        let mut finished: Vec<u32> = Vec::new();
+
+        self.walk(&mut Vec::new(), &mut |visit, _, node| match visit {
+            Visit::Enter => {
+                entered.push(values.len());
+                values.push(0);
+            }
+            Visit::Leave => {
+                let children = finished.split_off(finished.len() - node.children.len());
+                let value = if children.is_empty() {
+                    node.metadata.iter().sum()
+                } else {
+                    node.metadata
+                        .iter()
+                        .flat_map(|&index| children.get((index as usize).checked_sub(1)?))
+                        .sum()
+                };
+
+                values[entered.pop().unwrap()] = value;
+                finished.push(value);
+            }
+        });
+
+        values
+    }
+
+    /// Number of nodes in every subtree, in the order they are entered.
+    fn sizes(&self) -> Vec<usize> {
+        let mut sizes = Vec::new();
+        let mut entered = Vec::new();
+
+        self.walk(&mut Vec::new(), &mut |visit, _, _| match visit {
+            Visit::Enter => {
+                entered.push(sizes.len());
+                sizes.push(0);
+            }
+            Visit::Leave => {
+                let index = entered.pop().unwrap();
+                sizes[index] = sizes.len() - index;
+            }
+        });
+
+        sizes
+    }
+
+    /// The node reached by following child indexes from here.
+    fn get(&self, path: &[usize]) -> Option<&Node> {
+        path.iter()
+            .try_fold(self, |node, &child| node.children.get(child))
+    }
+
+    /// Visit this node and all below it depth first, children in order,
+    /// together with their paths from the root of the tree. Every node is
+    /// entered before its children and left after them.
+    fn walk<'a>(
+        &'a self,
+        path: &mut Vec<usize>,
+        visit: &mut impl FnMut(Visit, &[usize], &'a Node),
+    ) {
+        // Every node on the way down, and the next of its children to visit.
+        let mut stack = vec![(self, 0)];
+        visit(Visit::Enter, path, self);
+
+        while let Some(&(node, next)) = stack.last() {
+            match node.children.get(next) {
+                Some(child) => {
+                    stack.last_mut().unwrap().1 += 1;
+                    stack.push((child, 0));
+                    path.push(next);
+                    visit(Visit::Enter, path, child);
+                }
+                None => {
+                    visit(Visit::Leave, path, node);
+                    stack.pop();
+                    if !stack.is_empty() {
+                        path.pop();
+                    }
+                }
+            }
         }
     }
 
-    fn from_iter(iter: &mut impl Iterator<Item = u32>) -> Node {
-        let child_count = iter.next().expect("Invalid iteration. Input too small?") as usize;
-        let meta_count = iter.next().expect("Invalid iteration. Input too small?") as usize;
+    /// One line per node, indented by depth below this one, with its path,
+    /// value, depth, subtree size and metadata. `path` is where this node is
+    /// in the tree.
+    fn pretty(&self, path: &[usize]) -> String {
+        let (values, sizes) = (self.values(), self.sizes());
+        let top = path.len();
+        let mut out = String::new();
+        let mut index = 0;
 
-        let children = (0..child_count).map(|_| Node::from_iter(iter)).collect();
-        let metadata = iter.take(meta_count).collect();
+        self.walk(&mut path.to_vec(), &mut |visit, path, node| {
+            if visit == Visit::Enter {
+                out += &format!(
+                    "{}[{}] value {}, depth {}, size {}, metadata {:?}\n",
+                    "  ".repeat(path.len() - top),
+                    format_path(path),
+                    values[index],
+                    path.len(),
+                    sizes[index],
+                    node.metadata
+                );
+                index += 1;
+            }
+        });
 
-        Node { children, metadata }
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let values = self.values();
+        let mut json = String::new();
+        let mut index = 0;
+
+        self.walk(&mut Vec::new(), &mut |visit, path, node| match visit {
+            Visit::Enter => {
+                if path.last().is_some_and(|&i| i > 0) {
+                    json.push(',');
+                }
+                json += &format!(
+                    "{{\"value\":{},\"metadata\":{:?},\"children\":[",
+                    values[index], node.metadata
+                );
+                index += 1;
+            }
+            Visit::Leave => json += "]}",
+        });
+
+        json
+    }
+
+    /// Graphviz DOT with a node per tree node, named after its path.
+    fn to_dot(&self, path: &[usize]) -> String {
+        let name = |path: &[usize]| {
+            let parts: String = path.iter().map(|i| format!("_{}", i)).collect();
+            format!("n{}", parts)
+        };
+        let values = self.values();
+        let top = path.len();
+        let mut dot = String::from("digraph tree {\n");
+        let mut index = 0;
+
+        self.walk(&mut path.to_vec(), &mut |visit, path, node| {
+            if visit == Visit::Leave {
+                return;
+            }
+
+            dot += &format!(
+                "    {} [label=\"[{}]\\nvalue {}\\n{:?}\"];\n",
+                name(path),
+                format_path(path),
+                values[index],
+                node.metadata
+            );
+            index += 1;
+
+            if path.len() > top {
+                let parent = &path[..path.len() - 1];
+                dot += &format!("    {} -> {};\n", name(parent), name(path));
+            }
+        });
+
+        dot + "}\n"
+    }
+
+    /// The tree in the licence format: child count, metadata count, the
+    /// children and then the metadata, for every node.
+    fn to_numbers(&self) -> Vec<u32> {
+        let mut numbers = Vec::new();
+
+        self.walk(&mut Vec::new(), &mut |visit, _, node| match visit {
+            Visit::Enter => {
+                numbers.push(node.children.len() as u32);
+                numbers.push(node.metadata.len() as u32);
+            }
+            Visit::Leave => numbers.extend(&node.metadata),
+        });
+
+        numbers
+    }
+
+    /// Builds a tree from the licence format. Errors give the offset, in
+    /// numbers, where the input ended early or where unused numbers start.
+    fn from_numbers(numbers: impl IntoIterator<Item = u32>) -> Result<Node, String> {
+        // A node being read, with its children read so far.
+        struct Partial {
+            child_count: usize,
+            metadata_count: usize,
+            children: Vec<Node>,
+        }
+
+        // Where the next node goes: every node on the stack is waiting for
+        // the child after those it already has.
+        fn path(stack: &[Partial]) -> String {
+            let path: Vec<usize> = stack.iter().map(|p| p.children.len()).collect();
+            format_path(&path)
+        }
+
+        let mut numbers = numbers.into_iter();
+        let mut offset = 0;
+        let mut next = |what: &str, stack: &[Partial]| {
+            let n = numbers.next().ok_or_else(|| {
+                format!(
+                    "Input ended at offset {} reading the {} of node [{}]",
+                    offset,
+                    what,
+                    path(stack)
+                )
+            })?;
+            offset += 1;
+            Ok::<_, String>(n)
+        };
+
+        let mut stack: Vec<Partial> = Vec::new();
+        let root = loop {
+            let reading_child = match stack.last() {
+                Some(top) => top.children.len() < top.child_count,
+                None => true,
+            };
+
+            if reading_child {
+                let child_count = next("child count", &stack)? as usize;
+                let metadata_count = next("metadata count", &stack)? as usize;
+                stack.push(Partial {
+                    child_count,
+                    metadata_count,
+                    children: Vec::with_capacity(child_count.min(1024)),
+                });
+                continue;
+            }
+
+            // With the node off the stack, the path of the next node is its
+            // own again.
+            let Partial {
+                metadata_count,
+                children,
+                ..
+            } = stack.pop().unwrap();
+            let metadata = (0..metadata_count)
+                .map(|_| next("metadata", &stack))
+                .collect::<Result<_, _>>()?;
+
+            let node = Node { children, metadata };
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => break node,
+            }
+        };
+
+        match numbers.next() {
+            Some(_) => Err(format!("Unused numbers from offset {}", offset)),
+            None => Ok(root),
+        }
+    }
+}
+
+impl FromStr for Node {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s
+            .split_whitespace()
+            .enumerate()
+            .map(|(i, n)| {
+                n.parse()
+                    .map_err(|_| format!("Invalid number '{}' at offset {}", n, i))
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+
+        Node::from_numbers(numbers)
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let numbers: Vec<String> = self.to_numbers().iter().map(u32::to_string).collect();
+        write!(f, "{}", numbers.join(" "))
+    }
+}
+
+/// The licence format describes every tree exactly once, so comparing it
+/// compares the trees without recursing.
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_numbers() == other.to_numbers()
+    }
+}
+
+/// Frees the tree one node at a time instead of recursively.
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut nodes = std::mem::take(&mut self.children);
+
+        while let Some(mut node) = nodes.pop() {
+            nodes.append(&mut node.children);
+        }
+    }
+}
+
+/// Paths look like `0/2/1`, the third child of the first child of the root
+/// and then its second child. The root itself is the empty path.
+fn parse_path(s: &str) -> Result<Vec<usize>, String> {
+    s.split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().map_err(|_| format!("Invalid path {}", s)))
+        .collect()
+}
+
+fn format_path(path: &[usize]) -> String {
+    let parts: Vec<String> = path.iter().map(usize::to_string).collect();
+    parts.join("/")
+}
+
+#[derive(Debug, PartialEq)]
+enum Export {
+    Json,
+    Dot,
+}
+
+/// Command line flags. `--node PATH` picks a subtree, `--print` prints it
+/// with the value, depth and size of every node and `--export json|dot`
+/// prints it for other tools.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    node: Vec<usize>,
+    print: bool,
+    export: Option<Export>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+
+            match arg.as_str() {
+                "--node" => options.node = parse_path(&value()?)?,
+                "--print" => options.print = true,
+                "--export" => {
+                    options.export = Some(match value()?.as_str() {
+                        "json" => Export::Json,
+                        "dot" => Export::Dot,
+                        other => return Err(format!("Unknown export format {}", other)),
+                    })
+                }
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        Ok(options)
     }
 }
 
 fn main() {
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let mut iter = input.split_whitespace().flat_map(str::parse);
-    let root = Node::from_iter(&mut iter);
+    let root: Node = input.parse().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    if !options.print && options.export.is_none() {
+        println!("Part 1. Sum: {}", root.metadata_sum());
+        println!("Part 2. Value: {}", root.value());
+        return;
+    }
+
+    let node = root.get(&options.node).unwrap_or_else(|| {
+        eprintln!("No node at {}", format_path(&options.node));
+        std::process::exit(1);
+    });
+
+    if options.print {
+        print!("{}", node.pretty(&options.node));
+    }
 
-    println!("Part 1. Sum: {}", root.metadata_sum());
-    println!("Part 2. Value: {}", root.value());
+    match options.export {
+        Some(Export::Json) => println!("{}", node.to_json()),
+        Some(Export::Dot) => print!("{}", node.to_dot(&options.node)),
+        None => {}
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn get_iter() -> impl Iterator<Item = u32> {
-        "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2"
-            .split_whitespace()
-            .flat_map(str::parse)
+    const EXAMPLE: &str = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+
+    fn example() -> Node {
+        EXAMPLE.parse().unwrap()
     }
 
     #[test]
     fn test_part1() {
-        let root = Node::from_iter(&mut get_iter());
+        let root = example();
 
         assert_eq!(138, root.metadata_sum());
     }
 
     #[test]
     fn test_part2() {
-        let root = Node::from_iter(&mut get_iter());
+        let root = example();
 
         assert_eq!(66, root.value());
     }
+
+    #[test]
+    fn paths() {
+        let root = example();
+
+        assert_eq!(Ok(vec![1, 0]), parse_path("1/0"));
+        assert_eq!(Ok(vec![]), parse_path(""));
+        assert!(parse_path("1/x").is_err());
+
+        assert_eq!(Some(&root), root.get(&[]));
+        assert_eq!(vec![99], root.get(&[1, 0]).unwrap().metadata);
+        assert_eq!(None, root.get(&[2]));
+        assert_eq!(None, root.get(&[0, 0]));
+        assert_eq!("1/0", format_path(&[1, 0]));
+    }
+
+    #[test]
+    fn pretty() {
+        let root = example();
+
+        assert_eq!(
+            "[] value 66, depth 0, size 4, metadata [1, 1, 2]
+  [0] value 33, depth 1, size 1, metadata [10, 11, 12]
+  [1] value 0, depth 1, size 2, metadata [2]
+    [1/0] value 99, depth 2, size 1, metadata [99]
+",
+            root.pretty(&[])
+        );
+        assert_eq!(
+            "[1/0] value 99, depth 2, size 1, metadata [99]\n",
+            root.get(&[1, 0]).unwrap().pretty(&[1, 0])
+        );
+    }
+
+    #[test]
+    fn json() {
+        let root = example();
+
+        assert_eq!(
+            concat!(
+                r#"{"value":66,"metadata":[1, 1, 2],"children":["#,
+                r#"{"value":33,"metadata":[10, 11, 12],"children":[]},"#,
+                r#"{"value":0,"metadata":[2],"children":["#,
+                r#"{"value":99,"metadata":[99],"children":[]}]}]}"#
+            ),
+            root.to_json()
+        );
+    }
+
+    #[test]
+    fn dot() {
+        let root = example();
+
+        assert_eq!(
+            r#"digraph tree {
+    n [label="[]\nvalue 66\n[1, 1, 2]"];
+    n_0 [label="[0]\nvalue 33\n[10, 11, 12]"];
+    n -> n_0;
+    n_1 [label="[1]\nvalue 0\n[2]"];
+    n -> n_1;
+    n_1_0 [label="[1/0]\nvalue 99\n[99]"];
+    n_1 -> n_1_0;
+}
+"#,
+            root.to_dot(&[])
+        );
+
+        let subtree = root.get(&[1]).unwrap().to_dot(&[1]);
+        assert_eq!(2, subtree.matches("label").count());
+        assert_eq!(1, subtree.matches("->").count());
+    }
+
+    #[test]
+    fn truncated() {
+        let error = |s: &str| s.parse::<Node>().unwrap_err();
+
+        assert_eq!(
+            "Input ended at offset 0 reading the child count of node []",
+            error("")
+        );
+        assert_eq!(
+            "Input ended at offset 12 reading the metadata of node [1]",
+            error("2 3 0 3 10 11 12 1 1 0 1 99")
+        );
+        assert_eq!(
+            "Input ended at offset 10 reading the metadata count of node [1/0]",
+            error("2 3 0 3 10 11 12 1 1 0")
+        );
+        assert_eq!(
+            "Input ended at offset 15 reading the metadata of node []",
+            error(&EXAMPLE[..EXAMPLE.len() - 2])
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            "Unused numbers from offset 16",
+            format!("{} 4 5", EXAMPLE).parse::<Node>().unwrap_err()
+        );
+        assert_eq!(
+            "Invalid number 'x' at offset 2",
+            "1 1 x".parse::<Node>().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn zero_metadata_index() {
+        // Metadata 0 doesn't refer to any child, rather than underflowing.
+        let root: Node = "1 2 0 1 5 0 1".parse().unwrap();
+        assert_eq!(5, root.value());
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(EXAMPLE, example().to_string());
+
+        let mut rng = Rng(0x2018_0808);
+        for _ in 0..100 {
+            let numbers = random_tree(&mut rng, 6);
+            let root = Node::from_numbers(numbers.iter().copied()).unwrap();
+
+            assert_eq!(numbers, root.to_numbers());
+            assert_eq!(root, root.to_string().parse().unwrap());
+            assert_eq!(recursive_value(&root), root.value());
+        }
+    }
+
+    #[test]
+    fn deep_tree() {
+        // A chain of nodes far deeper than the stack could recurse.
+        let depth = 200_000;
+        let mut numbers = Vec::new();
+        for i in 0..depth {
+            numbers.extend(&[if i + 1 < depth { 1 } else { 0 }, 1]);
+        }
+        numbers.extend(std::iter::repeat_n(1, depth));
+
+        let root = Node::from_numbers(numbers.iter().copied()).unwrap();
+        assert_eq!(depth as u32, root.metadata_sum());
+        assert_eq!(1, root.value());
+        assert_eq!(depth, root.sizes()[0]);
+        assert_eq!(numbers, root.to_numbers());
+        assert!(root == root);
+    }
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u32) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % u64::from(n)) as u32
+        }
+    }
+
+    /// Numbers of a random tree at most `depth` deep.
+    fn random_tree(rng: &mut Rng, depth: u32) -> Vec<u32> {
+        let children = if depth == 0 { 0 } else { rng.below(4) };
+        let metadata = rng.below(4);
+        let mut numbers = vec![children, metadata];
+
+        for _ in 0..children {
+            numbers.extend(random_tree(rng, depth - 1));
+        }
+        numbers.extend((0..metadata).map(|_| rng.below(5)));
+
+        numbers
+    }
+
+    /// The value as the puzzle describes it, to check the stack based one.
+    fn recursive_value(node: &Node) -> u32 {
+        if node.children.is_empty() {
+            return node.metadata.iter().sum();
+        }
+
+        node.metadata
+            .iter()
+            .filter(|&&i| i >= 1)
+            .flat_map(|&i| node.children.get(i as usize - 1))
+            .map(recursive_value)
+            .sum()
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let options =
+            Options::from_args(args("--node 0/2/1 --print --export dot").into_iter()).unwrap();
+        assert_eq!(vec![0, 2, 1], options.node);
+        assert!(options.print);
+        assert_eq!(Some(Export::Dot), options.export);
+
+        assert_eq!(
+            Options::default(),
+            Options::from_args(args("").into_iter()).unwrap()
+        );
+        assert!(Options::from_args(args("--export xml").into_iter()).is_err());
+        assert!(Options::from_args(args("--node").into_iter()).is_err());
+    }
 }
@@ -1,77 +1,455 @@
-use std::collections::VecDeque;
+use std::fmt;
 use std::io::{stdin, Read};
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Game {
     players: usize,
     last_marble: usize,
+    /// The winning score, when the game is an example with a known answer.
+    high_score: Option<u64>,
+}
+
+impl Game {
+    /// The same game played until a marble `multiplier` times larger.
+    fn scaled(&self, multiplier: usize) -> Result<Game, String> {
+        let last_marble = self
+            .last_marble
+            .checked_mul(multiplier)
+            .filter(|&last| last <= MAX_MARBLE)
+            .ok_or(format!(
+                "Last marble {} x {} is too large",
+                self.last_marble, multiplier
+            ))?;
+
+        Ok(Game {
+            players: self.players,
+            last_marble,
+            high_score: None,
+        })
+    }
 }
 
 impl FromStr for Game {
-    type Err = ();
+    type Err = String;
+
+    /// Parses `N players; last marble is worth M points`, optionally followed
+    /// by `: high score is X` as in the examples. Punctuation and spacing
+    /// between the words don't matter.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut it = s.split_whitespace();
-        let players = it.next().unwrap().parse().unwrap();
-        let last_marble = it.nth(5).unwrap().parse().unwrap();
+        let words: Vec<&str> = s
+            .split(|c: char| c.is_whitespace() || c == ';' || c == ':' || c == ',')
+            .filter(|w| !w.is_empty())
+            .collect();
+
+        let number = |what: &str, word: &str| {
+            word.parse()
+                .map_err(|_| format!("Invalid {} '{}' in '{}'", what, word, s))
+        };
+
+        let (players, last_marble, rest) = match words.as_slice() {
+            [n, "players", "last", "marble", "is", "worth", m, "points", rest @ ..]
+            | [n, "player", "last", "marble", "is", "worth", m, "points", rest @ ..] => {
+                (number("player count", n)?, number("last marble", m)?, rest)
+            }
+            _ => {
+                return Err(format!(
+                    "Expected 'N players; last marble is worth M points' but found '{}'",
+                    s.trim()
+                ))
+            }
+        };
+
+        let high_score = match rest {
+            [] => None,
+            ["high", "score", "is", x] => Some(number("high score", x)? as u64),
+            _ => return Err(format!("Unexpected '{}' in '{}'", rest.join(" "), s.trim())),
+        };
+
+        if players == 0 {
+            return Err(format!("No players in '{}'", s));
+        }
+        if last_marble > MAX_MARBLE {
+            return Err(format!(
+                "Last marble {} is too large in '{}'",
+                last_marble, s
+            ));
+        }
 
         Ok(Game {
             players,
             last_marble,
+            high_score,
         })
     }
 }
 
-fn run(game: &Game) -> u32 {
-    let mut player_scores = vec![0; game.players + 1];
-    let mut circle = VecDeque::with_capacity(game.last_marble);
-    circle.push_front(0);
+/// Every game in `input`, one per line, skipping blank lines.
+fn parse_games(input: &str) -> Result<Vec<Game>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| line.parse().map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
+}
+
+/// How marbles are placed and scored. The puzzle keeps every 23rd marble,
+/// together with the one 7 marbles counter-clockwise of the current one, and
+/// places all others between the marbles 1 and 2 steps clockwise.
+#[derive(Clone, Debug, PartialEq)]
+struct Rules {
+    /// Marbles divisible by this are kept instead of placed.
+    special: usize,
+    /// Steps counter-clockwise to the marble removed by a special marble.
+    back: usize,
+    /// Steps clockwise to the marble a new marble is placed after.
+    forward: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            special: 23,
+            back: 7,
+            forward: 1,
+        }
+    }
+}
+
+impl Rules {
+    fn validate(&self) -> Result<(), String> {
+        // With every marble special, the first one would empty the circle.
+        if self.special < 2 {
+            return Err(format!(
+                "Special modulus {} must be at least 2",
+                self.special
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// The circle as a doubly linked ring. Marbles are numbered from 0 upwards,
+/// so their numbers double as indexes into the links, and placing or
+/// removing a marble only touches its neighbours. Links are stored as `u32`
+/// to halve the memory of long games.
+#[derive(Clone, Debug)]
+struct Circle {
+    next: Vec<u32>,
+    prev: Vec<u32>,
+    current: usize,
+}
+
+/// Highest marble number a circle can hold.
+const MAX_MARBLE: usize = u32::MAX as usize;
+
+impl Circle {
+    /// A circle with only marble 0, with room for marbles up to `last`.
+    fn new(last: usize) -> Self {
+        assert!(last <= MAX_MARBLE, "Marble {} does not fit a circle", last);
+
+        Circle {
+            next: vec![0; last + 1],
+            prev: vec![0; last + 1],
+            current: 0,
+        }
+    }
+
+    fn next(&self, marble: usize) -> usize {
+        self.next[marble] as usize
+    }
+
+    fn prev(&self, marble: usize) -> usize {
+        self.prev[marble] as usize
+    }
+
+    fn clockwise(&self, marble: usize, steps: usize) -> usize {
+        (0..steps).fold(marble, |m, _| self.next(m))
+    }
+
+    fn counter_clockwise(&self, marble: usize, steps: usize) -> usize {
+        (0..steps).fold(marble, |m, _| self.prev(m))
+    }
+
+    /// Place `marble` right after `after` and make it the current marble.
+    fn insert_after(&mut self, after: usize, marble: usize) {
+        let before = self.next(after);
+
+        self.next[after] = marble as u32;
+        self.prev[marble] = after as u32;
+        self.next[marble] = before as u32;
+        self.prev[before] = marble as u32;
+        self.current = marble;
+    }
+
+    /// Take `marble` out, making the one clockwise of it current.
+    fn remove(&mut self, marble: usize) {
+        let (prev, next) = (self.prev(marble), self.next(marble));
+
+        self.next[prev] = next as u32;
+        self.prev[next] = prev as u32;
+        self.current = next;
+    }
+
+    /// Marbles in clockwise order, starting with marble 0, or with the
+    /// current marble once marble 0 has been taken out.
+    fn marbles(&self) -> Vec<usize> {
+        let start = match self.next(self.prev(0)) == 0 {
+            true => 0,
+            false => self.current,
+        };
+        let mut marbles = vec![start];
+        let mut marble = self.next(start);
+
+        while marble != start {
+            marbles.push(marble);
+            marble = self.next(marble);
+        }
+
+        marbles
+    }
+}
+
+impl fmt::Display for Circle {
+    /// Clockwise from marble 0 where it is still in the circle, with the
+    /// current marble in parentheses.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let marbles: Vec<String> = self
+            .marbles()
+            .into_iter()
+            .map(|m| match m == self.current {
+                true => format!("({})", m),
+                false => m.to_string(),
+            })
+            .collect();
+
+        write!(f, "{}", marbles.join(" "))
+    }
+}
+
+/// A game in progress.
+struct Play<'a> {
+    rules: &'a Rules,
+    circle: Circle,
+    /// Score of every player, player 1 first.
+    scores: Vec<u64>,
+    /// The last marble played.
+    marble: usize,
+}
 
-    // Player turns go in cycles but the game plays until the last marble.
-    let players = (1..).take(game.players).cycle();
-    let marbles = (1..).take(game.last_marble);
+impl<'a> Play<'a> {
+    fn new(game: &Game, rules: &'a Rules) -> Self {
+        rules.validate().unwrap_or_else(|e| panic!("{}", e));
 
-    // Initial, naive solution to just calculate an index in a Vec and insert
-    // (rearranging the poor vec every time) didn't really work for part 2...
-    // Instead use a double ended queue and rotate it so insert/remove ops are
-    // always done at the front.
-    for (player, marble) in players.zip(marbles) {
-        if marble % 23 == 0 {
-            circle.rotate_right(7); // Safe because circle.len() >= 22.
-            let replaced_marble = circle.pop_front().unwrap();
-            player_scores[player] += replaced_marble + marble;
+        Play {
+            rules,
+            circle: Circle::new(game.last_marble),
+            scores: vec![0; game.players],
+            marble: 0,
+        }
+    }
+
+    fn turn(&mut self) {
+        self.marble += 1;
+        let marble = self.marble;
+        let player = (marble - 1) % self.scores.len();
+        let current = self.circle.current;
+
+        if marble.is_multiple_of(self.rules.special) {
+            let removed = self.circle.counter_clockwise(current, self.rules.back);
+            self.circle.remove(removed);
+            self.scores[player] += (marble + removed) as u64;
         } else {
-            // Try to rotate two steps. Rotation > circle.len() panics.
-            circle.rotate_left(2.min(circle.len()));
-            circle.push_front(marble);
+            let after = self.circle.clockwise(current, self.rules.forward);
+            self.circle.insert_after(after, marble);
+        }
+    }
+
+    /// Play turns until `marble` has been played.
+    fn until(&mut self, marble: usize) {
+        while self.marble < marble {
+            self.turn();
+        }
+    }
+}
+
+/// Final scores of a game.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    /// Score of every player, player 1 first.
+    scores: Vec<u64>,
+}
+
+impl Outcome {
+    /// The winning player, numbered from 1, and their score. Ties go to the
+    /// lowest numbered player.
+    fn winner(&self) -> (usize, u64) {
+        let (index, &score) = self
+            .scores
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, s)| s)
+            .unwrap();
+
+        (index + 1, score)
+    }
+}
+
+fn play(game: &Game, rules: &Rules) -> Outcome {
+    let mut play = Play::new(game, rules);
+    play.until(game.last_marble);
+
+    Outcome {
+        scores: play.scores,
+    }
+}
+
+/// The circle right after `marble` has been played.
+fn snapshot(game: &Game, rules: &Rules, marble: usize) -> Result<Circle, String> {
+    if marble > game.last_marble {
+        return Err(format!(
+            "Marble {} is never played, the last marble is {}",
+            marble, game.last_marble
+        ));
+    }
+
+    let mut play = Play::new(game, rules);
+    play.until(marble);
+
+    Ok(play.circle)
+}
+
+/// Play every game and print its winner, comparing with the expected high
+/// score where there is one. True if they all matched.
+fn batch(games: &[Game], rules: &Rules) -> bool {
+    let mut matched = true;
+
+    for game in games {
+        let (player, score) = play(game, rules).winner();
+        print!(
+            "{} players, last marble {}: high score {} by player {}",
+            game.players, game.last_marble, score, player
+        );
+
+        match game.high_score {
+            Some(expected) if expected != score => {
+                println!(", expected {}", expected);
+                matched = false;
+            }
+            Some(_) => println!(", as expected"),
+            None => println!(),
         }
     }
 
-    *player_scores.iter().max().unwrap()
+    matched
+}
+
+/// Command line flags. `--special N`, `--back N` and `--forward N` change
+/// the rules, `--snapshot N` prints the circle after marble N and
+/// `--multiplier N` scales the last marble for part 2. `--batch FILE` plays
+/// every game in a file instead, checking the high scores that are given.
+#[derive(Debug, PartialEq)]
+struct Options {
+    rules: Rules,
+    snapshot: Option<usize>,
+    multiplier: usize,
+    batch: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rules: Rules::default(),
+            snapshot: None,
+            multiplier: 100,
+            batch: None,
+        }
+    }
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or(format!("Missing value for {}", arg))?;
+            let number = || {
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid number {}", value))
+            };
+
+            match arg.as_str() {
+                "--special" => options.rules.special = number()?,
+                "--back" => options.rules.back = number()?,
+                "--forward" => options.rules.forward = number()?,
+                "--snapshot" => options.snapshot = Some(number()?),
+                "--multiplier" => options.multiplier = number()?,
+                "--batch" => options.batch = Some(value),
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        options.rules.validate()?;
+        Ok(options)
+    }
 }
 
 fn main() {
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let rules = &options.rules;
+    let fail = |e: String| -> ! {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    };
+
+    if let Some(path) = &options.batch {
+        let input = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| fail(format!("Failed to read {}: {}", path, e)));
+        let games = parse_games(&input).unwrap_or_else(|e| fail(e));
+
+        if !batch(&games, rules) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let mut game: Game = input.parse().unwrap();
-    let part1 = run(&game);
+    let game: Game = input.trim().parse().unwrap_or_else(|e| fail(e));
+
+    if let Some(marble) = options.snapshot {
+        let circle = snapshot(&game, rules, marble).unwrap_or_else(|e| fail(e));
+        println!("{}", circle);
+        return;
+    }
+
+    let part1 = play(&game, rules);
 
-    game.last_marble *= 100;
-    let part2 = run(&game);
+    let game = game.scaled(options.multiplier).unwrap_or_else(|e| fail(e));
+    let part2 = play(&game, rules);
 
-    println!("Part 1. High score: {}", part1);
-    println!("Part 2. High score: {}", part2);
+    for (part, outcome) in [(1, part1), (2, part2)].iter() {
+        let (player, score) = outcome.winner();
+        println!("Part {}. High score: {} by player {}", part, score, player);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::run;
+    use super::*;
 
     #[rustfmt::skip]
-    fn data() -> Vec<(String,u32)> {
+    fn data() -> Vec<(String,u64)> {
         vec![
             (" 9 players; last marble is worth   25 points".into(), 32),
             ("10 players; last marble is worth 1618 points".into(), 8_317),
@@ -87,7 +465,7 @@ mod tests {
     fn test(data_index: usize) {
         let (test, expected) = &data()[data_index];
         let game = test.parse().unwrap();
-        assert_eq!(*expected, run(&game));
+        assert_eq!(*expected, play(&game, &Rules::default()).winner().1);
     }
 
     #[test]
@@ -119,4 +497,206 @@ mod tests {
     fn example_7() {
         test(5);
     }
+
+    fn example() -> Game {
+        Game {
+            players: 9,
+            last_marble: 25,
+            high_score: None,
+        }
+    }
+
+    #[test]
+    fn score_table() {
+        let outcome = play(&example(), &Rules::default());
+
+        // Player 5 keeps marble 23 and takes marble 9 with it.
+        assert_eq!(vec![0, 0, 0, 0, 32, 0, 0, 0, 0], outcome.scores);
+        assert_eq!((5, 32), outcome.winner());
+    }
+
+    #[test]
+    fn snapshots() {
+        let rules = Rules::default();
+        let game = example();
+
+        assert_eq!("(0)", snapshot(&game, &rules, 0).unwrap().to_string());
+        assert_eq!(
+            "0 (4) 2 1 3",
+            snapshot(&game, &rules, 4).unwrap().to_string()
+        );
+        assert_eq!(
+            "0 16 8 17 4 18 (19) 2 20 10 21 5 22 11 1 12 6 13 3 14 7 15",
+            snapshot(&game, &rules, 23).unwrap().to_string()
+        );
+        assert_eq!(
+            "0 16 8 17 4 18 19 2 24 20 (25) 10 21 5 22 11 1 12 6 13 3 14 7 15",
+            snapshot(&game, &rules, 25).unwrap().to_string()
+        );
+        assert!(snapshot(&game, &rules, 26).is_err());
+    }
+
+    #[test]
+    fn marble_0_removed() {
+        // Marble 3 takes marble 0, the one before the current marble.
+        let rules = Rules {
+            special: 3,
+            back: 1,
+            forward: 1,
+        };
+        let game = Game {
+            players: 2,
+            last_marble: 5,
+            high_score: None,
+        };
+
+        assert_eq!("0 (2) 1", snapshot(&game, &rules, 2).unwrap().to_string());
+        assert_eq!("(2) 1", snapshot(&game, &rules, 3).unwrap().to_string());
+        assert_eq!("(5) 1 4 2", snapshot(&game, &rules, 5).unwrap().to_string());
+    }
+
+    #[test]
+    fn custom_rules() {
+        // Every other marble is special and takes the current marble itself.
+        let rules = Rules {
+            special: 2,
+            back: 0,
+            forward: 0,
+        };
+        let game = Game {
+            players: 2,
+            last_marble: 6,
+            high_score: None,
+        };
+
+        // 1 goes after 0, 2 takes 1, 3 goes after 0, 4 takes 3 and so on.
+        assert_eq!(vec![0, 2 + 1 + 4 + 3 + 6 + 5], play(&game, &rules).scores);
+        assert_eq!("(0)", snapshot(&game, &rules, 6).unwrap().to_string());
+        assert!(Rules {
+            special: 1,
+            ..rules
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn wider_placement() {
+        let rules = Rules {
+            forward: 2,
+            ..Rules::default()
+        };
+        let game = example();
+
+        // Marbles go after the one two steps clockwise instead.
+        assert_eq!("0 1 (2)", snapshot(&game, &rules, 2).unwrap().to_string());
+        assert_eq!("0 1 (3) 2", snapshot(&game, &rules, 3).unwrap().to_string());
+    }
+
+    #[test]
+    fn parse() {
+        let game: Game = "10 players; last marble is worth 1618 points"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            (10, 1618, None),
+            (game.players, game.last_marble, game.high_score)
+        );
+
+        let game: Game = "  1 player;last marble is worth 8 points: high score is 7\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            (1, 8, Some(7)),
+            (game.players, game.last_marble, game.high_score)
+        );
+
+        assert_eq!(
+            Ok(Game {
+                players: 10,
+                last_marble: 1618,
+                high_score: Some(8317),
+            }),
+            "10 players; last marble is worth 1618 points; high score is 8317".parse()
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |s: &str| s.parse::<Game>().unwrap_err();
+
+        assert!(error("").starts_with("Expected"));
+        assert!(error("ten players; last marble is worth 5 points").contains("'ten'"));
+        assert!(error("10 players; last marble is worth 5").starts_with("Expected"));
+        assert!(error("10 players; last marble is worth 5 points; and more").contains("'and more'"));
+        assert!(
+            error("10 players; last marble is worth 5 points: high score is x")
+                .contains("high score")
+        );
+        assert!(error("0 players; last marble is worth 5 points").contains("No players"));
+        assert!(error("9 players; last marble is worth 4294967296 points").contains("too large"));
+        assert!(
+            parse_games("\n9 players; last marble is worth 25 points\nnonsense")
+                .unwrap_err()
+                .starts_with("Line 3")
+        );
+    }
+
+    #[test]
+    fn examples_file() {
+        let games = parse_games(include_str!("../examples.txt")).unwrap();
+        assert_eq!(6, games.len());
+        assert!(batch(&games, &Rules::default()));
+
+        let wrong = "9 players; last marble is worth 25 points: high score is 31";
+        assert!(!batch(&parse_games(wrong).unwrap(), &Rules::default()));
+    }
+
+    #[test]
+    fn scaled() {
+        let game = Game {
+            high_score: Some(32),
+            ..example()
+        };
+        assert_eq!(
+            Ok(Game {
+                players: 9,
+                last_marble: 250,
+                high_score: None,
+            }),
+            game.scaled(10)
+        );
+        assert!(game.scaled(usize::MAX).is_err());
+        assert!(game.scaled(MAX_MARBLE / 25 + 1).is_err());
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let options =
+            Options::from_args(args("--special 5 --back 2 --forward 3 --snapshot 40").into_iter())
+                .unwrap();
+        assert_eq!(
+            Rules {
+                special: 5,
+                back: 2,
+                forward: 3
+            },
+            options.rules
+        );
+        assert_eq!(Some(40), options.snapshot);
+
+        assert_eq!(100, options.multiplier);
+        assert_eq!(None, options.batch);
+
+        let options =
+            Options::from_args(args("--multiplier 10 --batch games.txt").into_iter()).unwrap();
+        assert_eq!(10, options.multiplier);
+        assert_eq!(Some("games.txt".to_string()), options.batch);
+
+        assert!(Options::from_args(args("--special 1").into_iter()).is_err());
+        assert!(Options::from_args(args("--back").into_iter()).is_err());
+        assert!(Options::from_args(args("--back x").into_iter()).is_err());
+    }
 }
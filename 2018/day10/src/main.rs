@@ -1,15 +1,17 @@
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::io::{stdin, Read};
+use std::fs::File;
+use std::io::{self, stdin, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Coordinate {
     x: i32,
     y: i32,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Point {
     position: Coordinate,
     velocity: Coordinate,
@@ -20,6 +22,13 @@ impl Point {
         self.position.x += self.velocity.x;
         self.position.y += self.velocity.y;
     }
+
+    fn position_at(&self, seconds: i32) -> Coordinate {
+        Coordinate {
+            x: self.position.x + self.velocity.x * seconds,
+            y: self.position.y + self.velocity.y * seconds,
+        }
+    }
 }
 
 impl FromStr for Point {
@@ -50,13 +59,53 @@ impl Points {
         self.0.iter_mut().for_each(Point::transform);
     }
 
+    /// Points as they will be after `seconds` (which may be negative).
+    fn at(&self, seconds: i32) -> Points {
+        Points(
+            self.0
+                .iter()
+                .map(|p| Point {
+                    position: p.position_at(seconds),
+                    velocity: p.velocity.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Second at which the bounding box is the smallest, i.e. when the
+    /// message is readable.
+    fn convergence(&self) -> i32 {
+        let mut points = self.at(0);
+
+        // Fast forward until area is small enough for memory and visual output.
+        let mut seconds = 0;
+        while points.dimensions().area > 2000 {
+            points.transform_all();
+            seconds += 1;
+        }
+
+        let mut area = points.dimensions().area;
+        loop {
+            points.transform_all();
+
+            let a = points.dimensions().area;
+            if a > area {
+                // Grid is growing, break.
+                return seconds;
+            }
+
+            area = a;
+            seconds += 1;
+        }
+    }
+
     fn dimensions(&self) -> Dimensions {
         let points = &self.0;
 
-        let mut min_x: i32 = std::i32::MAX;
-        let mut min_y: i32 = std::i32::MAX;
-        let mut max_x: i32 = std::i32::MIN;
-        let mut max_y: i32 = std::i32::MIN;
+        let mut min_x: i32 = i32::MAX;
+        let mut min_y: i32 = i32::MAX;
+        let mut max_x: i32 = i32::MIN;
+        let mut max_y: i32 = i32::MIN;
 
         for &Point {
             position: Coordinate { x, y },
@@ -71,7 +120,7 @@ impl Points {
 
         let width = (max_x - min_x) as u32;
         let height = (max_y - min_y) as u32;
-        let area = width * height;
+        let area = u64::from(width) * u64::from(height);
 
         Dimensions {
             min_x,
@@ -82,17 +131,27 @@ impl Points {
         }
     }
 
-    fn grid(&self) -> Grid {
+    fn grid(&self) -> Result<Grid, String> {
+        self.grid_within(&self.dimensions())
+    }
+
+    /// Draw the points inside a fixed viewport. Points outside of it are
+    /// left out, which keeps every frame of a sweep the same size.
+    fn grid_within(&self, dimensions: &Dimensions) -> Result<Grid, String> {
         let Dimensions {
             min_x,
             min_y,
             width,
             height,
             area,
-            ..
-        } = self.dimensions();
+        } = *dimensions;
 
-        assert!(area < 100_000, "Area must be less than 100 000");
+        if area >= MAX_AREA {
+            return Err(format!(
+                "Area {}x{} is too large to draw, it must be less than {}",
+                width, height, MAX_AREA
+            ));
+        }
 
         let mut grid = vec![vec!['.'; width as usize + 1]; height as usize + 1];
 
@@ -100,15 +159,135 @@ impl Points {
             let column = point.position.x - min_x;
             let row = point.position.y - min_y;
 
+            if column < 0 || row < 0 || column > width as i32 || row > height as i32 {
+                continue;
+            }
+
             grid[row as usize][column as usize] = '#';
         }
 
-        Grid(grid)
+        Ok(Grid(grid))
     }
 }
 
+/// Largest area that gets drawn.
+const MAX_AREA: u64 = 100_000;
+
+/// Cells around the message left in view when a frame is too spread out to
+/// draw in full.
+const CLIP_MARGIN: u32 = 20;
+
 struct Grid(Vec<Vec<char>>);
 
+impl Grid {
+    fn width(&self) -> usize {
+        self.0.first().map_or(0, Vec::len)
+    }
+
+    fn height(&self) -> usize {
+        self.0.len()
+    }
+
+    fn is_lit(&self, row: usize, column: usize) -> bool {
+        self.0[row][column] == '#'
+    }
+
+    /// Write the grid as an image, every cell scaled up to `scale` x `scale`
+    /// pixels.
+    fn write_image<W: Write>(
+        &self,
+        format: ImageFormat,
+        scale: usize,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let width = self.width() * scale;
+        let height = self.height() * scale;
+
+        let pixels = (0..height).map(|y| {
+            (0..width)
+                .map(|x| self.is_lit(y / scale, x / scale))
+                .collect::<Vec<bool>>()
+        });
+
+        match format {
+            ImageFormat::Pbm => {
+                // Raw PBM, one bit per pixel where 1 is black.
+                write!(out, "P4\n{} {}\n", width, height)?;
+                for row in pixels {
+                    let bytes: Vec<u8> = row
+                        .chunks(8)
+                        .map(|bits| {
+                            bits.iter()
+                                .enumerate()
+                                .filter(|(_, &lit)| lit)
+                                .fold(0, |byte, (i, _)| byte | 0x80 >> i)
+                        })
+                        .collect();
+                    out.write_all(&bytes)?;
+                }
+            }
+            ImageFormat::Ppm => {
+                write!(out, "P6\n{} {}\n255\n", width, height)?;
+                for row in pixels {
+                    let bytes: Vec<u8> = row
+                        .into_iter()
+                        .flat_map(|lit| if lit { LIT } else { DARK })
+                        .collect();
+                    out.write_all(&bytes)?;
+                }
+            }
+            ImageFormat::Png => {
+                // 8 bit grayscale, every scanline prefixed with filter type 0.
+                let mut raw = Vec::with_capacity((width + 1) * height);
+                for row in pixels {
+                    raw.push(0);
+                    raw.extend(row.into_iter().map(|lit| if lit { 255 } else { 0 }));
+                }
+
+                png::write(out, width, height, 0, &raw)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const LIT: [u8; 3] = [0xff, 0xcc, 0x33];
+const DARK: [u8; 3] = [0x0f, 0x0f, 0x23];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ImageFormat {
+    Pbm,
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Pbm => "pbm",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pbm" => Ok(ImageFormat::Pbm),
+            "ppm" => Ok(ImageFormat::Ppm),
+            "png" => Ok(ImageFormat::Png),
+            _ => Err(format!("Unknown image format {}", s)),
+        }
+    }
+}
+
 impl Display for Grid {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let grid = &self.0;
@@ -124,46 +303,179 @@ impl Display for Grid {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Dimensions {
     min_x: i32,
     min_y: i32,
     width: u32,
     height: u32,
-    area: u32,
+    area: u64,
 }
 
-fn main() {
-    let mut input = String::new();
-    stdin()
-        .read_to_string(&mut input)
-        .expect("Failed to read stdin");
+impl Dimensions {
+    fn padded(&self, margin: u32) -> Self {
+        let width = self.width + 2 * margin;
+        let height = self.height + 2 * margin;
 
-    let mut points = Points(input.lines().flat_map(str::parse).collect());
+        Dimensions {
+            min_x: self.min_x - margin as i32,
+            min_y: self.min_y - margin as i32,
+            width,
+            height,
+            area: u64::from(width) * u64::from(height),
+        }
+    }
 
-    // Fast forward until area is small enough for memory and visual output.
-    let mut seconds = 0;
-    while points.dimensions().area > 2000 {
-        points.transform_all();
-        seconds += 1;
+    /// Padded by `margin`, or by as much of it as still leaves an area small
+    /// enough to draw.
+    fn padded_within(&self, margin: u32) -> Self {
+        // Padding by more than this on every side is too large whatever the
+        // size of the message.
+        let most = (MAX_AREA as f64).sqrt() as u32 / 2;
+
+        (0..=margin.min(most))
+            .rev()
+            .map(|margin| self.padded(margin))
+            .find(|padded| padded.area < MAX_AREA)
+            .unwrap_or(*self)
     }
+}
 
-    let mut area = points.dimensions().area;
-    loop {
-        let grid = points.grid();
-        points.transform_all();
+/// Image export requested on the command line.
+///
+/// `--at <seconds> --out <file>` writes a single frame, with the format taken
+/// from the file extension. Frames too spread out to draw in full are clipped
+/// to the area around the message. `--sweep <window> --out <dir>` writes one frame
+/// per second from `window` seconds before convergence to `window` seconds
+/// after it, showing the message padded by `window` cells or as much of that
+/// as can be drawn. `--format` and `--scale` change the output format and
+/// pixel size.
+#[derive(Debug, Default, PartialEq)]
+struct Export {
+    at: Option<i32>,
+    sweep: Option<u32>,
+    out: Option<PathBuf>,
+    format: Option<ImageFormat>,
+    scale: Option<usize>,
+}
+
+impl Export {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut export = Export::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
 
-        let a = points.dimensions().area;
-        if a > area {
-            // Grid is growing, break.
-            println!("{}", &grid);
-            break;
+            match arg.as_str() {
+                "--at" => export.at = Some(parse(&value()?)?),
+                "--sweep" => export.sweep = Some(parse(&value()?)?),
+                "--out" => export.out = Some(value()?.into()),
+                "--format" => export.format = Some(value()?.parse()?),
+                "--scale" => export.scale = Some(parse(&value()?)?),
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
         }
 
-        area = a;
-        seconds += 1;
+        if (export.at.is_some() || export.sweep.is_some()) && export.out.is_none() {
+            return Err("--out is required when exporting images".into());
+        }
+        if export.scale == Some(0) {
+            return Err("Scale must be at least 1".into());
+        }
+
+        Ok(export)
     }
 
+    fn is_requested(&self) -> bool {
+        self.at.is_some() || self.sweep.is_some()
+    }
+
+    fn run(&self, points: &Points, convergence: i32) -> Result<(), String> {
+        let out = self.out.as_ref().expect("Missing output path");
+        let scale = self.scale.unwrap_or(4);
+
+        if let Some(seconds) = self.at {
+            let format = self
+                .format
+                .or_else(|| ImageFormat::from_path(out))
+                .unwrap_or(ImageFormat::Png);
+
+            let frame = points.at(seconds);
+            let grid = match frame.grid() {
+                Ok(grid) => grid,
+                Err(_) => {
+                    let viewport = points
+                        .at(convergence)
+                        .dimensions()
+                        .padded_within(CLIP_MARGIN);
+                    println!("Clipping second {} to the message", seconds);
+                    frame.grid_within(&viewport)?
+                }
+            };
+
+            write_image(&grid, format, scale, out)?;
+            println!("Wrote second {} to {}", seconds, out.display());
+        }
+
+        if let Some(window) = self.sweep {
+            let format = self.format.unwrap_or(ImageFormat::Png);
+            let message = points.at(convergence).dimensions();
+            let viewport = message.padded_within(window);
+            if u64::from(viewport.width) < u64::from(message.width) + 2 * u64::from(window) {
+                println!("Clipping the sweep to the area around the message");
+            }
+
+            std::fs::create_dir_all(out)
+                .map_err(|e| format!("Failed to create {}: {}", out.display(), e))?;
+
+            let start = convergence - window as i32;
+            let end = convergence + window as i32;
+            for seconds in start..=end {
+                let path = out.join(format!("{:06}.{}", seconds, format.extension()));
+
+                let grid = points.at(seconds).grid_within(&viewport)?;
+                write_image(&grid, format, scale, &path)?;
+            }
+
+            println!("Wrote seconds {} to {} to {}", start, end, out.display());
+        }
+
+        Ok(())
+    }
+}
+
+fn write_image(grid: &Grid, format: ImageFormat, scale: usize, path: &Path) -> Result<(), String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .and_then(|mut file| grid.write_image(format, scale, &mut file))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn parse<T: FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("Invalid number {}", s))
+}
+
+fn main() {
+    let fail = |e: String| -> ! {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    };
+    let export = Export::from_args(std::env::args().skip(1)).unwrap_or_else(|e| fail(e));
+
+    let mut input = String::new();
+    stdin()
+        .read_to_string(&mut input)
+        .expect("Failed to read stdin");
+
+    let points = Points(input.lines().flat_map(str::parse).collect());
+    let seconds = points.convergence();
+
+    println!("{}", points.at(seconds).grid().unwrap_or_else(|e| fail(e)));
     println!("Message would have arrived in {} seconds.", seconds);
+
+    if export.is_requested() {
+        export.run(&points, seconds).unwrap_or_else(|e| fail(e));
+    }
 }
 
 #[cfg(test)]
@@ -239,7 +551,7 @@ position=<-3,  6> velocity=< 2, -1>"
     fn grid_0() {
         let points = points();
 
-        let grid = points.grid();
+        let grid = points.grid().unwrap();
         assert_eq!(
             "........#.............
 ................#.....
@@ -266,7 +578,7 @@ position=<-3,  6> velocity=< 2, -1>"
     fn grid_1() {
         let mut points = points();
         points.transform_all();
-        let grid = points.grid();
+        let grid = points.grid().unwrap();
 
         assert_eq!(
             "........#....#....
@@ -290,7 +602,7 @@ position=<-3,  6> velocity=< 2, -1>"
     fn grid_2() {
         let mut points = points();
         transform_points_n(&mut points, 2);
-        let grid = points.grid();
+        let grid = points.grid().unwrap();
 
         assert_eq!(
             "..........#...
@@ -312,7 +624,7 @@ position=<-3,  6> velocity=< 2, -1>"
     fn grid_3() {
         let mut points = points();
         transform_points_n(&mut points, 3);
-        let grid = points.grid();
+        let grid = points.grid().unwrap();
 
         assert_eq!(
             "#...#..###
@@ -328,11 +640,123 @@ position=<-3,  6> velocity=< 2, -1>"
         );
     }
 
+    #[test]
+    fn convergence() {
+        assert_eq!(3, points().convergence());
+    }
+
+    #[test]
+    fn at() {
+        let mut points = points();
+        let at_3 = points.at(3).grid().unwrap().to_string();
+        transform_points_n(&mut points, 3);
+
+        assert_eq!(points.grid().unwrap().to_string(), at_3);
+        assert_eq!(
+            points.at(-3).grid().unwrap().to_string(),
+            self::points().grid().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn grid_within() {
+        let points = points().at(3);
+        let viewport = points.dimensions().padded(1);
+        let grid = points.grid_within(&viewport).unwrap();
+
+        assert_eq!(12, grid.width());
+        assert_eq!(10, grid.height());
+        assert!(grid.is_lit(1, 1));
+        assert!(!grid.is_lit(0, 0));
+    }
+
+    #[test]
+    fn too_large() {
+        let points = points().at(-100_000);
+        assert!(points.grid().is_err());
+
+        // Clipped to the message, only the points that have strayed back
+        // into view are drawn.
+        let viewport = self::points().at(3).dimensions().padded(CLIP_MARGIN);
+        let grid = points.grid_within(&viewport).unwrap();
+        assert_eq!(50, grid.width());
+        assert_eq!(48, grid.height());
+
+        // A sweep window too wide to draw is cut down to what fits.
+        let message = self::points().at(3).dimensions();
+        assert_eq!(message.padded(20), message.padded_within(20));
+        let clipped = message.padded_within(u32::MAX);
+        assert!(clipped.area < MAX_AREA);
+        assert!(message.padded((clipped.width - message.width) / 2 + 1).area >= MAX_AREA);
+    }
+
+    #[test]
+    fn pbm() {
+        let grid = points().at(3).grid().unwrap();
+        let mut out = Vec::new();
+        grid.write_image(ImageFormat::Pbm, 1, &mut out).unwrap();
+
+        let header = b"P4\n10 8\n";
+        assert_eq!(header, &out[..header.len()]);
+        // Two bytes per row of ten pixels.
+        assert_eq!(header.len() + 2 * 8, out.len());
+        // #...#..###
+        assert_eq!(
+            &[0b1000_1001, 0b1100_0000],
+            &out[header.len()..header.len() + 2]
+        );
+    }
+
+    #[test]
+    fn ppm() {
+        let grid = points().at(3).grid().unwrap();
+        let mut out = Vec::new();
+        grid.write_image(ImageFormat::Ppm, 2, &mut out).unwrap();
+
+        let header = b"P6\n20 16\n255\n";
+        assert_eq!(header, &out[..header.len()]);
+        assert_eq!(header.len() + 20 * 16 * 3, out.len());
+        assert_eq!(&LIT, &out[header.len()..header.len() + 3]);
+        assert_eq!(&DARK, &out[header.len() + 6..header.len() + 9]);
+    }
+
+    #[test]
+    fn png() {
+        let grid = points().at(3).grid().unwrap();
+        let mut out = Vec::new();
+        grid.write_image(ImageFormat::Png, 1, &mut out).unwrap();
+
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &out[..8]);
+        assert_eq!(b"IHDR", &out[12..16]);
+        assert_eq!(&10u32.to_be_bytes(), &out[16..20]);
+        assert_eq!(&8u32.to_be_bytes(), &out[20..24]);
+        assert_eq!(b"IEND", &out[out.len() - 8..out.len() - 4]);
+    }
+
+    #[test]
+    fn export_args() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let export = Export::from_args(args("--sweep 5 --out frames --format ppm").into_iter())
+            .expect("Valid arguments");
+        assert_eq!(Some(5), export.sweep);
+        assert_eq!(Some(ImageFormat::Ppm), export.format);
+        assert_eq!(Some(PathBuf::from("frames")), export.out);
+
+        assert!(Export::from_args(args("--at 3").into_iter()).is_err());
+        assert!(Export::from_args(args("--at").into_iter()).is_err());
+        assert!(Export::from_args(args("--format gif").into_iter()).is_err());
+        assert!(Export::from_args(args("--at 3 --out a.png --scale 0").into_iter()).is_err());
+        assert!(!Export::from_args(args("").into_iter())
+            .unwrap()
+            .is_requested());
+    }
+
     #[test]
     fn grid_4() {
         let mut points = points();
         transform_points_n(&mut points, 4);
-        let grid = points.grid();
+        let grid = points.grid().unwrap();
 
         assert_eq!(
             "........#....
@@ -1,6 +1,11 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::io::{stdin, Read};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
 
 const GRID_SIDE: usize = 300;
 
@@ -29,13 +34,91 @@ impl Display for Coordinate {
     }
 }
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 struct Square {
     top_left: Coordinate,
     size: u32,
     sum: i32,
 }
 
+#[derive(Debug, Default, PartialEq)]
+struct Rectangle {
+    top_left: Coordinate,
+    width: u32,
+    height: u32,
+    sum: i32,
+}
+
+impl From<Rectangle> for Square {
+    fn from(rect: Rectangle) -> Self {
+        assert_eq!(rect.width, rect.height, "Rectangle is not a square");
+
+        Square {
+            top_left: rect.top_left,
+            size: rect.width,
+            sum: rect.sum,
+        }
+    }
+}
+
+/// Width and height of a window to search for, in cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Window {
+    width: usize,
+    height: usize,
+}
+
+impl Window {
+    fn square(size: usize) -> Self {
+        Window {
+            width: size,
+            height: size,
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid window {}, expected WxH", s);
+
+        let mut parts = s.split('x');
+        let width = parts
+            .next()
+            .and_then(|w| w.parse().ok())
+            .ok_or_else(invalid)?;
+        let height = parts
+            .next()
+            .and_then(|h| h.parse().ok())
+            .ok_or_else(invalid)?;
+
+        if parts.next().is_some() || width == 0 || height == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Window { width, height })
+    }
+}
+
+/// Inclusive range of square sizes to consider.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SizeRange {
+    min: usize,
+    max: usize,
+}
+
+/// Every zero indexed top left cell (x, y) where `window` fits in a grid of
+/// `width` x `height` cells. A window placed at (x, y) covers the columns
+/// `x..=x + window.width - 1` and the rows `y..=y + window.height - 1`, so
+/// the last placement in a row is at `width - window.width`, flush with the
+/// right edge, and a window as large as the grid has exactly one placement.
+fn placements(width: usize, height: usize, window: Window) -> impl Iterator<Item = (usize, usize)> {
+    let columns = (width + 1).saturating_sub(window.width);
+    let rows = (height + 1).saturating_sub(window.height);
+
+    (0..rows).flat_map(move |y| (0..columns).map(move |x| (x, y)))
+}
+
 fn power_level(coord: Coordinate, serial: Serial) -> i32 {
     let rack_id = coord.x + 10;
     let mut power = rack_id * coord.y;
@@ -48,13 +131,17 @@ fn power_level(coord: Coordinate, serial: Serial) -> i32 {
     power - 5
 }
 
-struct PowerGrid(Grid);
+struct PowerGrid {
+    cells: Grid,
+    width: usize,
+    height: usize,
+}
 
 impl PowerGrid {
-    fn new(serial: Serial) -> Self {
-        let mut grid = vec![vec![0; GRID_SIDE]; GRID_SIDE];
+    fn with_size(serial: Serial, width: usize, height: usize) -> Self {
+        let mut cells = vec![vec![0; width]; height];
 
-        for (y, row) in grid.iter_mut().enumerate() {
+        for (y, row) in cells.iter_mut().enumerate() {
             for (x, column) in row.iter_mut().enumerate() {
                 let coord = Coordinate::from_zero_index(x, y);
 
@@ -62,116 +149,402 @@ impl PowerGrid {
             }
         }
 
-        PowerGrid(grid)
+        PowerGrid {
+            cells,
+            width,
+            height,
+        }
     }
 
     fn largest_square_by_size(&self, size: usize) -> Square {
-        let mut largest_sum = std::i32::MIN;
-        let mut top_left = Coordinate::default();
+        self.largest_window(Window::square(size)).into()
+    }
 
-        let limit = GRID_SIDE - size;
+    fn largest_window(&self, window: Window) -> Rectangle {
+        let mut largest_sum = i32::MIN;
+        let mut top_left = Coordinate::default();
 
-        for y in 0..limit {
-            for x in 0..limit {
-                let mut sum = 0;
+        for (x, y) in placements(self.width, self.height, window) {
+            let mut sum = 0;
 
-                for y in (y..).take(size) {
-                    for x in (x..).take(size) {
-                        sum += self.0[y][x];
-                    }
-                }
+            for row in &self.cells[y..y + window.height] {
+                sum += row[x..x + window.width].iter().sum::<i32>();
+            }
 
-                if sum > largest_sum {
-                    largest_sum = sum;
-                    top_left = Coordinate::from_zero_index(x, y);
-                }
+            if sum > largest_sum {
+                largest_sum = sum;
+                top_left = Coordinate::from_zero_index(x, y);
             }
         }
 
-        Square {
+        Rectangle {
             top_left,
             sum: largest_sum,
-            size: size as u32,
+            width: window.width as u32,
+            height: window.height as u32,
         }
     }
 
     fn sum_area_table(&self) -> SumAreaTable {
-        let mut table = vec![vec![0; GRID_SIDE]; GRID_SIDE];
-        for y in 0..GRID_SIDE {
-            for x in 0..GRID_SIDE {
-                table[y][x] = {
-                    let above = if y > 0 { table[y - 1][x] } else { 0 };
-                    let left = if x > 0 { table[y][x - 1] } else { 0 };
-
-                    let prev = if y > 0 && x > 0 {
-                        table[y - 1][x - 1]
-                    } else {
-                        0
-                    };
-
-                    self.0[y][x] + above + left - prev
-                };
+        self.table_of(|power| power)
+    }
+
+    /// Table over only the positive cells. The positive sum of a region is an
+    /// upper bound for what adding that region to a square can contribute.
+    fn positive_area_table(&self) -> SumAreaTable {
+        self.table_of(|power| power.max(0))
+    }
+
+    fn table_of(&self, value: impl Fn(i32) -> i32) -> SumAreaTable {
+        // One extra row and column of zeroes on the top and left, so that
+        // queries never have to special case the edges.
+        let mut table = vec![vec![0; self.width + 1]; self.height + 1];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                table[y + 1][x + 1] =
+                    value(self.cells[y][x]) + table[y][x + 1] + table[y + 1][x] - table[y][x];
             }
         }
 
-        SumAreaTable(table)
+        SumAreaTable {
+            table,
+            width: self.width,
+            height: self.height,
+        }
     }
 }
 
-struct SumAreaTable(Grid);
+struct SumAreaTable {
+    table: Grid,
+    width: usize,
+    height: usize,
+}
 
 impl SumAreaTable {
-    fn largest_square_by_size(&self, size: usize) -> Square {
-        let table = &self.0;
-        let limit = GRID_SIDE - size;
+    /// Sum of the `width` x `height` rectangle whose top left cell is at the
+    /// zero indexed (`left`, `top`). Constant time regardless of size.
+    fn sum(&self, left: usize, top: usize, width: usize, height: usize) -> i32 {
+        let table = &self.table;
+        let (right, bottom) = (left + width, top + height);
 
-        let mut largest_sum = std::i32::MIN;
-        let mut top_left = Coordinate::default();
+        table[bottom][right] - table[top][right] - table[bottom][left] + table[top][left]
+    }
 
-        for top in 0..limit {
-            for left in 0..limit {
-                let bottom = top + size;
-                let right = left + size;
+    fn largest_window(&self, window: Window) -> Rectangle {
+        let mut largest_sum = i32::MIN;
+        let mut top_left = Coordinate::default();
 
-                let sum = table[top][left] - table[top][right] - table[bottom][left]
-                    + table[bottom][right];
+        for (x, y) in placements(self.width, self.height, window) {
+            let sum = self.sum(x, y, window.width, window.height);
 
-                if sum > largest_sum {
-                    largest_sum = sum;
-                    top_left = Coordinate::from_zero_index(left + 1, top + 1);
-                }
+            if sum > largest_sum {
+                largest_sum = sum;
+                top_left = Coordinate::from_zero_index(x, y);
             }
         }
 
-        Square {
+        Rectangle {
             top_left,
             sum: largest_sum,
-            size: size as u32,
+            width: window.width as u32,
+            height: window.height as u32,
+        }
+    }
+
+    /// Best squares for every size in `search.sizes`, evaluated in full.
+    /// Sizes are spread over the threads, every thread keeping its own top
+    /// list until they are merged at the end.
+    fn top_squares(&self, search: &Search) -> Vec<Square> {
+        let threads = search.threads.max(1);
+
+        let lists = thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|offset| {
+                    scope.spawn(move || {
+                        let mut top = TopSquares::new(search.top);
+
+                        for size in (search.sizes.min..=search.sizes.max)
+                            .skip(offset)
+                            .step_by(threads)
+                        {
+                            let window = Window::square(size);
+
+                            for (x, y) in placements(self.width, self.height, window) {
+                                top.push(self.sum(x, y, size, size), size, x, y);
+                            }
+                        }
+
+                        top
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("Search thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        merge(search.top, lists)
+    }
+
+    /// Same result as `top_squares`, but grows squares from every top left
+    /// corner and stops as soon as the positive cells left within reach
+    /// cannot lift any larger square above the current top list. Rows are
+    /// spread over the threads, which share the best threshold found so far.
+    fn top_squares_pruned(&self, positive: &SumAreaTable, search: &Search) -> Vec<Square> {
+        let threads = search.threads.max(1);
+        let shared_threshold = AtomicI32::new(i32::MIN);
+
+        let lists = thread::scope(|scope| {
+            let shared_threshold = &shared_threshold;
+
+            let handles: Vec<_> = (0..threads)
+                .map(|offset| {
+                    scope.spawn(move || {
+                        let mut top = TopSquares::new(search.top);
+
+                        for y in (0..self.height).skip(offset).step_by(threads) {
+                            for x in 0..self.width {
+                                let max_size =
+                                    (self.width - x).min(self.height - y).min(search.sizes.max);
+
+                                if max_size < search.sizes.min {
+                                    continue;
+                                }
+
+                                let reachable = positive.sum(x, y, max_size, max_size);
+
+                                for size in search.sizes.min..=max_size {
+                                    let sum = self.sum(x, y, size, size);
+                                    top.push(sum, size, x, y);
+
+                                    if let Some(threshold) = top.threshold() {
+                                        shared_threshold.fetch_max(threshold, Ordering::Relaxed);
+                                    }
+
+                                    // Only the positive cells outside this square
+                                    // can add to any larger square from here.
+                                    let bound = sum + reachable - positive.sum(x, y, size, size);
+                                    if bound < shared_threshold.load(Ordering::Relaxed) {
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+
+                        top
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("Search thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        merge(search.top, lists)
+    }
+}
+
+/// How to search for the largest squares in part 2.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Search {
+    sizes: SizeRange,
+    /// Number of squares to report.
+    top: usize,
+    threads: usize,
+}
+
+/// Ranks squares by sum, breaking ties by the smallest size and then the
+/// top left corner in reading order. The same order as a sequential search
+/// that only replaces its best square on a strictly larger sum.
+type Rank = (i32, Reverse<usize>, Reverse<usize>, Reverse<usize>);
+
+/// The `capacity` best squares seen so far.
+struct TopSquares {
+    capacity: usize,
+    heap: BinaryHeap<Reverse<Rank>>,
+}
+
+impl TopSquares {
+    fn new(capacity: usize) -> Self {
+        TopSquares {
+            capacity,
+            heap: BinaryHeap::with_capacity(capacity + 1),
         }
     }
 
-    fn find_largest_square(&self) -> Square {
-        let mut largest_square = Square::default();
+    /// Add a square with a zero indexed top left corner.
+    fn push(&mut self, sum: i32, size: usize, x: usize, y: usize) {
+        let rank = (sum, Reverse(size), Reverse(y), Reverse(x));
 
-        for size in (1..).take(GRID_SIDE) {
-            let sq = self.largest_square_by_size(size);
-            if sq.sum > largest_square.sum {
-                largest_square = sq;
+        if self.heap.len() == self.capacity {
+            match self.heap.peek() {
+                Some(Reverse(worst)) if rank > *worst => {
+                    self.heap.pop();
+                }
+                _ => return,
             }
         }
 
-        largest_square
+        self.heap.push(Reverse(rank));
+    }
+
+    /// Sum a square must exceed to make the list, once it is full.
+    fn threshold(&self) -> Option<i32> {
+        if self.heap.len() < self.capacity {
+            return None;
+        }
+
+        self.heap.peek().map(|Reverse((sum, ..))| *sum)
+    }
+
+    fn into_squares(self) -> Vec<Square> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(
+                |Reverse((sum, Reverse(size), Reverse(y), Reverse(x)))| Square {
+                    top_left: Coordinate::from_zero_index(x, y),
+                    size: size as u32,
+                    sum,
+                },
+            )
+            .collect()
+    }
+}
+
+fn merge(capacity: usize, lists: Vec<TopSquares>) -> Vec<Square> {
+    let mut top = TopSquares::new(capacity);
+
+    for list in lists {
+        for Reverse((sum, Reverse(size), Reverse(y), Reverse(x))) in list.heap {
+            top.push(sum, size, x, y);
+        }
+    }
+
+    top.into_squares()
+}
+
+/// Variations of the puzzle, given as command line flags.
+///
+/// `--width` and `--height` set the grid dimensions, `--min-size` and
+/// `--max-size` limit the squares considered in part 2 and `--window WxH`
+/// additionally searches for the largest rectangle of that shape.
+/// `--top K` reports the K best squares, `--threads N` sets the number of
+/// search threads and `--prune` stops growing squares that cannot win.
+#[derive(Debug, PartialEq)]
+struct Options {
+    width: usize,
+    height: usize,
+    min_size: usize,
+    max_size: Option<usize>,
+    window: Option<Window>,
+    top: usize,
+    threads: Option<usize>,
+    prune: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            width: GRID_SIDE,
+            height: GRID_SIDE,
+            min_size: 1,
+            max_size: None,
+            window: None,
+            top: 1,
+            threads: None,
+            prune: false,
+        }
     }
 }
 
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+
+            match arg.as_str() {
+                "--width" => options.width = parse(&value()?)?,
+                "--height" => options.height = parse(&value()?)?,
+                "--min-size" => options.min_size = parse(&value()?)?,
+                "--max-size" => options.max_size = Some(parse(&value()?)?),
+                "--window" => options.window = Some(value()?.parse()?),
+                "--top" => options.top = parse(&value()?)?,
+                "--threads" => options.threads = Some(parse(&value()?)?),
+                "--prune" => options.prune = true,
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+
+        if options.top == 0 || options.threads == Some(0) {
+            return Err("--top and --threads must be at least 1".into());
+        }
+
+        let side = options.width.min(options.height);
+        let max_size = options.max_size.unwrap_or(side);
+
+        if options.min_size == 0 || options.min_size > max_size || max_size > side {
+            return Err(format!(
+                "Square sizes {}..={} do not fit in a {}x{} grid",
+                options.min_size, max_size, options.width, options.height
+            ));
+        }
+
+        if let Some(w) = options.window {
+            if w.width > options.width || w.height > options.height {
+                return Err(format!(
+                    "Window {}x{} does not fit in a {}x{} grid",
+                    w.width, w.height, options.width, options.height
+                ));
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn sizes(&self) -> SizeRange {
+        SizeRange {
+            min: self.min_size,
+            max: self.max_size.unwrap_or_else(|| self.width.min(self.height)),
+        }
+    }
+
+    fn search(&self) -> Search {
+        let threads = self
+            .threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+        Search {
+            sizes: self.sizes(),
+            top: self.top,
+            threads,
+        }
+    }
+}
+
+fn parse(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("Invalid number {}", s))
+}
+
 fn main() {
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let serial = input.parse().expect("Serial is not a valid number");
-    let grid = PowerGrid::new(serial);
+    let serial = input.trim().parse().expect("Serial is not a valid number");
+    let grid = PowerGrid::with_size(serial, options.width, options.height);
 
     // I learned about summed area tables from the subreddit. A much faster
     // solution for part 2 than to calculate the sum for every subgrid.
@@ -183,7 +556,13 @@ fn main() {
 
     // But for part 2 where there are many more, and varied grids:
     // Use a sum area table for part 2.
-    let part2 = table.find_largest_square();
+    let search = options.search();
+    let squares = if options.prune {
+        table.top_squares_pruned(&grid.positive_area_table(), &search)
+    } else {
+        table.top_squares(&search)
+    };
+    let part2 = &squares[0];
 
     println!(
         "Part 1. Largest 3x3 square's top left coordinate is {}. Total power {}.",
@@ -194,6 +573,25 @@ fn main() {
         "Part 2. Largest {0}x{0} square's top left coordinate is {1}. Total power {2}.",
         part2.size, part2.top_left, part2.sum
     );
+
+    for (i, square) in squares.iter().enumerate().skip(1) {
+        println!(
+            "{0:>7}. {1}x{1} square at {2}. Total power {3}.",
+            i + 1,
+            square.size,
+            square.top_left,
+            square.sum
+        );
+    }
+
+    if let Some(window) = options.window {
+        let rect = table.largest_window(window);
+
+        println!(
+            "Largest {}x{} rectangle's top left coordinate is {}. Total power {}.",
+            rect.width, rect.height, rect.top_left, rect.sum
+        );
+    }
 }
 
 #[cfg(test)]
@@ -244,13 +642,13 @@ mod tests {
             size: 3,
         };
 
-        let grid = PowerGrid::new(18);
+        let grid = PowerGrid::with_size(18, GRID_SIDE, GRID_SIDE);
         let square1 = grid.largest_square_by_size(3);
 
         assert_eq!(expected, square1);
 
         let table = grid.sum_area_table();
-        let square2 = table.largest_square_by_size(3);
+        let square2 = Square::from(table.largest_window(Window::square(3)));
 
         assert_eq!(
             square1, square2,
@@ -266,17 +664,343 @@ mod tests {
             size: 3,
         };
 
-        let grid = PowerGrid::new(42);
+        let grid = PowerGrid::with_size(42, GRID_SIDE, GRID_SIDE);
         let square1 = grid.largest_square_by_size(3);
 
         assert_eq!(expected, square1);
 
         let table = grid.sum_area_table();
-        let square2 = table.largest_square_by_size(3);
+        let square2 = Square::from(table.largest_window(Window::square(3)));
 
         assert_eq!(
             square1, square2,
             "Largest square from sum table did not match largest square from power grid"
         );
     }
+
+    #[test]
+    fn sum_matches_cells() {
+        let grid = PowerGrid::with_size(18, 40, 25);
+        let table = grid.sum_area_table();
+
+        for &(left, top, width, height) in
+            &[(0, 0, 1, 1), (3, 7, 5, 2), (0, 0, 40, 25), (39, 24, 1, 1)]
+        {
+            let expected: i32 = grid.cells[top..top + height]
+                .iter()
+                .flat_map(|row| &row[left..left + width])
+                .sum();
+
+            assert_eq!(expected, table.sum(left, top, width, height));
+        }
+    }
+
+    #[test]
+    fn rectangular_grid() {
+        let grid = PowerGrid::with_size(18, 60, 50);
+        assert_eq!(50, grid.cells.len());
+        assert_eq!(60, grid.cells[0].len());
+
+        let full = PowerGrid::with_size(18, GRID_SIDE, GRID_SIDE);
+        assert_eq!(full.cells[49][..60], grid.cells[49][..]);
+    }
+
+    #[test]
+    fn windows() {
+        let grid = PowerGrid::with_size(42, GRID_SIDE, GRID_SIDE);
+        let table = grid.sum_area_table();
+
+        for &window in &[
+            Window::square(3),
+            Window {
+                width: 4,
+                height: 2,
+            },
+            Window {
+                width: 1,
+                height: 7,
+            },
+        ] {
+            assert_eq!(grid.largest_window(window), table.largest_window(window));
+        }
+    }
+
+    fn search(min: usize, max: usize, top: usize, threads: usize) -> Search {
+        Search {
+            sizes: SizeRange { min, max },
+            top,
+            threads,
+        }
+    }
+
+    #[test]
+    fn size_range() {
+        let table = PowerGrid::with_size(18, GRID_SIDE, GRID_SIDE).sum_area_table();
+
+        let squares = table.top_squares(&search(3, 3, 1, 1));
+        assert_eq!(Coordinate { x: 33, y: 45 }, squares[0].top_left);
+        assert_eq!(3, squares[0].size);
+    }
+
+    #[test]
+    fn top_squares_match_sequential() {
+        let grid = PowerGrid::with_size(42, 60, 45);
+        let table = grid.sum_area_table();
+
+        let best: Vec<Square> = (1..=45)
+            .map(|size| Square::from(table.largest_window(Window::square(size))))
+            .collect();
+        let best_sum = best.iter().map(|sq| sq.sum).max().unwrap();
+        let expected = best.into_iter().find(|sq| sq.sum == best_sum).unwrap();
+
+        for threads in 1..=4 {
+            let squares = table.top_squares(&search(1, 45, 1, threads));
+            assert_eq!(vec![expected.clone()], squares);
+        }
+    }
+
+    #[test]
+    fn top_k() {
+        let grid = PowerGrid::with_size(18, 50, 50);
+        let table = grid.sum_area_table();
+
+        let squares = table.top_squares(&search(2, 20, 10, 3));
+        assert_eq!(10, squares.len());
+        assert!(squares.windows(2).all(|w| w[0].sum >= w[1].sum));
+
+        // Every reported square really has the reported sum.
+        for sq in &squares {
+            let (x, y) = (sq.top_left.x as usize - 1, sq.top_left.y as usize - 1);
+            let size = sq.size as usize;
+            assert_eq!(sq.sum, table.sum(x, y, size, size));
+        }
+    }
+
+    #[test]
+    fn pruned_matches_full_search() {
+        for &serial in &[18, 42, 6392] {
+            let grid = PowerGrid::with_size(serial, 80, 70);
+            let table = grid.sum_area_table();
+            let positive = grid.positive_area_table();
+
+            for &(top, threads) in &[(1, 1), (5, 2), (20, 3)] {
+                let s = search(1, 70, top, threads);
+                assert_eq!(
+                    table.top_squares(&s),
+                    table.top_squares_pruned(&positive, &s)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let options = Options::from_args(
+            args("--width 50 --height 40 --max-size 20 --window 3x5").into_iter(),
+        )
+        .expect("Valid arguments");
+        assert_eq!(SizeRange { min: 1, max: 20 }, options.sizes());
+        assert_eq!(
+            Some(Window {
+                width: 3,
+                height: 5
+            }),
+            options.window
+        );
+
+        assert_eq!(SizeRange { min: 1, max: 300 }, Options::default().sizes());
+
+        assert!(Options::from_args(args("--width 10 --max-size 20").into_iter()).is_err());
+        assert!(Options::from_args(args("--min-size 0").into_iter()).is_err());
+        assert!(Options::from_args(args("--window 3by5").into_iter()).is_err());
+        assert!(Options::from_args(args("--top 0").into_iter()).is_err());
+
+        let options = Options::from_args(args("--prune --top 3 --threads 2").into_iter())
+            .expect("Valid arguments");
+        assert!(options.prune);
+        assert_eq!(search(1, 300, 3, 2), options.search());
+        assert!(Options::from_args(args("--width 10 --window 11x1").into_iter()).is_err());
+    }
+
+    fn grid_of(cells: Grid) -> PowerGrid {
+        PowerGrid {
+            width: cells[0].len(),
+            height: cells.len(),
+            cells,
+        }
+    }
+
+    #[test]
+    fn placements_are_inclusive() {
+        let all: Vec<_> = placements(3, 2, Window::square(2)).collect();
+        assert_eq!(vec![(0, 0), (1, 0)], all);
+
+        assert_eq!(
+            1,
+            placements(
+                5,
+                4,
+                Window {
+                    width: 5,
+                    height: 4
+                }
+            )
+            .count()
+        );
+        assert_eq!(0, placements(5, 4, Window::square(5)).count());
+        assert_eq!(20, placements(5, 4, Window::square(1)).count());
+    }
+
+    #[test]
+    fn square_in_bottom_right_corner() {
+        let mut cells = vec![vec![-5; 6]; 4];
+        cells[3][5] = 4;
+        cells[2][5] = 4;
+        let grid = grid_of(cells);
+        let table = grid.sum_area_table();
+
+        let expected = Rectangle {
+            top_left: Coordinate { x: 6, y: 3 },
+            width: 1,
+            height: 2,
+            sum: 8,
+        };
+        let window = Window {
+            width: 1,
+            height: 2,
+        };
+
+        assert_eq!(expected, grid.largest_window(window));
+        assert_eq!(expected, table.largest_window(window));
+    }
+
+    #[test]
+    fn square_in_top_left_corner() {
+        let mut cells = vec![vec![-5; 4]; 4];
+        cells[0][0] = 4;
+        let grid = grid_of(cells);
+        let table = grid.sum_area_table();
+
+        let expected = Square {
+            top_left: Coordinate { x: 1, y: 1 },
+            size: 1,
+            sum: 4,
+        };
+
+        assert_eq!(expected, grid.largest_square_by_size(1));
+        assert_eq!(vec![expected], table.top_squares(&search(1, 4, 1, 2)));
+    }
+
+    #[test]
+    fn whole_grid_square() {
+        let grid = grid_of(vec![vec![4; 3]; 3]);
+        let table = grid.sum_area_table();
+        let positive = grid.positive_area_table();
+
+        let expected = Square {
+            top_left: Coordinate { x: 1, y: 1 },
+            size: 3,
+            sum: 36,
+        };
+
+        assert_eq!(expected, grid.largest_square_by_size(3));
+        assert_eq!(
+            vec![expected.clone()],
+            table.top_squares(&search(1, 3, 1, 1))
+        );
+        assert_eq!(
+            vec![expected],
+            table.top_squares_pruned(&positive, &search(1, 3, 1, 1))
+        );
+    }
+
+    /// Small xorshift generator, so the property tests are reproducible
+    /// without any dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn between(&mut self, low: usize, high: usize) -> usize {
+            low + self.below(high - low + 1)
+        }
+    }
+
+    #[test]
+    fn property_windows_match_brute_force() {
+        let mut rng = Rng(0x2018_1111);
+
+        for _ in 0..200 {
+            let serial = rng.between(1, 10_000) as Serial;
+            let (width, height) = (rng.between(1, 40), rng.between(1, 40));
+            let window = Window {
+                width: rng.between(1, width),
+                height: rng.between(1, height),
+            };
+
+            let grid = PowerGrid::with_size(serial, width, height);
+            let table = grid.sum_area_table();
+
+            assert_eq!(
+                grid.largest_window(window),
+                table.largest_window(window),
+                "serial {} grid {}x{} window {:?}",
+                serial,
+                width,
+                height,
+                window
+            );
+        }
+    }
+
+    #[test]
+    fn property_top_squares_match_brute_force() {
+        let mut rng = Rng(0x0bad_cafe);
+
+        for _ in 0..50 {
+            let serial = rng.between(1, 10_000) as Serial;
+            let (width, height) = (rng.between(1, 30), rng.between(1, 30));
+            let side = width.min(height);
+            let min = rng.between(1, side);
+            let max = rng.between(min, side);
+
+            let grid = PowerGrid::with_size(serial, width, height);
+            let table = grid.sum_area_table();
+            let positive = grid.positive_area_table();
+
+            // Brute force: the first strictly larger square wins, smallest
+            // size first.
+            let mut expected = Square {
+                sum: i32::MIN,
+                ..Square::default()
+            };
+            for size in min..=max {
+                let square = grid.largest_square_by_size(size);
+                if square.sum > expected.sum {
+                    expected = square;
+                }
+            }
+
+            let s = search(min, max, 1, rng.between(1, 4));
+            let message = format!(
+                "serial {} grid {}x{} sizes {}..={}",
+                serial, width, height, min, max
+            );
+
+            assert_eq!(vec![expected.clone()], table.top_squares(&s), "{}", message);
+            assert_eq!(
+                vec![expected],
+                table.top_squares_pruned(&positive, &s),
+                "{}",
+                message
+            );
+        }
+    }
 }
@@ -0,0 +1,479 @@
+//! One dimensional cellular automata. The plant rules generalised to any odd
+//! neighbourhood width, any number of cell states and Wolfram style rule
+//! numbers, with the space-time diagram rendered as text or an image.
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+pub type State = u8;
+
+/// Largest rule table we are willing to allocate.
+const MAX_TABLE: usize = 1 << 24;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Automaton {
+    states: u8,
+    width: usize,
+    /// Next state for every neighbourhood, indexed by reading the
+    /// neighbourhood as a base `states` number with the leftmost cell as the
+    /// most significant digit.
+    table: Vec<State>,
+}
+
+impl Automaton {
+    /// An automaton where every neighbourhood turns into state 0.
+    pub fn new(states: u8, width: usize) -> Result<Self, String> {
+        if states < 2 {
+            return Err(format!("Need at least 2 states, got {}", states));
+        }
+
+        if width.is_multiple_of(2) {
+            return Err(format!("Neighbourhood width {} is not odd", width));
+        }
+
+        let size = (0..width)
+            .try_fold(1usize, |size, _| size.checked_mul(states as usize))
+            .filter(|&size| size <= MAX_TABLE)
+            .ok_or(format!(
+                "{} states with neighbourhood width {} is too large",
+                states, width
+            ))?;
+
+        Ok(Automaton {
+            states,
+            width,
+            table: vec![0; size],
+        })
+    }
+
+    /// The rule numbering popularised by Wolfram: digit `i` of `rule` in base
+    /// `states` is the next state for the neighbourhood with index `i`. Rule
+    /// 110 is `wolfram(110, 2, 3)`.
+    pub fn wolfram(rule: u128, states: u8, width: usize) -> Result<Self, String> {
+        let mut automaton = Automaton::new(states, width)?;
+        let base = u128::from(states);
+
+        let mut rest = rule;
+        for next in automaton.table.iter_mut() {
+            *next = (rest % base) as State;
+            rest /= base;
+        }
+
+        if rest != 0 {
+            return Err(format!(
+                "Rule {} is too large for {} states and neighbourhood width {}",
+                rule, states, width
+            ));
+        }
+
+        Ok(automaton)
+    }
+
+    pub fn set(&mut self, neighbourhood: &[State], next: State) -> Result<(), String> {
+        if neighbourhood.len() != self.width {
+            return Err(format!(
+                "Neighbourhood of {} cells, expected {}",
+                neighbourhood.len(),
+                self.width
+            ));
+        }
+
+        if let Some(&s) = neighbourhood
+            .iter()
+            .chain(Some(&next))
+            .find(|&&s| s >= self.states)
+        {
+            return Err(format!("State {} out of range 0..{}", s, self.states));
+        }
+
+        let index = self.index(neighbourhood.iter().copied());
+        self.table[index] = next;
+        Ok(())
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    fn index(&self, cells: impl Iterator<Item = State>) -> usize {
+        cells.fold(0, |index, s| index * self.states as usize + s as usize)
+    }
+
+    /// Advance a single generation. Cells outside of the stored row all have
+    /// the background state, which evolves like any other cell would when
+    /// surrounded by itself.
+    pub fn step(&self, row: &Row) -> Row {
+        let radius = (self.width / 2) as i64;
+        let size = self.table.len();
+        let start = row.start - radius;
+        let len = row.cells.len() + self.width - 1;
+
+        // Everything left of the new row is background, so the window can be
+        // primed with all but the rightmost cell of the first neighbourhood.
+        let mut index = self.index(std::iter::repeat_n(row.background, self.width - 1));
+
+        let cells = (start..start + len as i64)
+            .map(|position| {
+                index = (index * self.states as usize + row.get(position + radius) as usize) % size;
+                self.table[index]
+            })
+            .collect();
+
+        let background = self.table[self.index(std::iter::repeat_n(row.background, self.width))];
+
+        Row {
+            cells,
+            start,
+            background,
+        }
+        .normalised()
+    }
+
+    /// The starting row followed by `generations` more.
+    pub fn run(&self, row: &Row, generations: usize) -> Vec<Row> {
+        let mut rows = vec![row.normalised()];
+
+        for _ in 0..generations {
+            let next = self.step(rows.last().unwrap());
+            rows.push(next);
+        }
+
+        rows
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Row {
+    cells: Vec<State>,
+    /// Position of the first stored cell.
+    start: i64,
+    /// State of every cell outside of `cells`.
+    background: State,
+}
+
+impl Row {
+    pub fn new(cells: Vec<State>) -> Self {
+        Row {
+            cells,
+            start: 0,
+            background: 0,
+        }
+    }
+
+    pub fn get(&self, position: i64) -> State {
+        let i = position - self.start;
+
+        if i < 0 {
+            self.background
+        } else {
+            *self.cells.get(i as usize).unwrap_or(&self.background)
+        }
+    }
+
+    /// Positions of the first and last cell that differ from the background.
+    pub fn bounds(&self) -> Option<(i64, i64)> {
+        let first = self.cells.iter().position(|&s| s != self.background)?;
+        let last = self.cells.iter().rposition(|&s| s != self.background)?;
+
+        Some((self.start + first as i64, self.start + last as i64))
+    }
+
+    /// Strip background cells from both ends.
+    fn normalised(&self) -> Self {
+        match self.bounds() {
+            Some((first, last)) => Row {
+                cells: (first..=last).map(|p| self.get(p)).collect(),
+                start: first,
+                background: self.background,
+            },
+            None => Row {
+                cells: Vec::new(),
+                start: 0,
+                background: self.background,
+            },
+        }
+    }
+}
+
+/// Rows of a run drawn one below the other, from position `from` to `to`.
+pub struct Diagram<'a> {
+    rows: &'a [Row],
+    states: u8,
+    from: i64,
+    to: i64,
+}
+
+impl<'a> Diagram<'a> {
+    /// Diagram wide enough to show every non background cell of every row.
+    pub fn new(rows: &'a [Row], states: u8) -> Self {
+        let (from, to) = rows
+            .iter()
+            .filter_map(Row::bounds)
+            .fold((0, 0), |(from, to), (first, last)| {
+                (from.min(first), to.max(last))
+            });
+
+        Diagram {
+            rows,
+            states,
+            from,
+            to,
+        }
+    }
+
+    fn symbol(&self, state: State) -> char {
+        match (self.states, state) {
+            (2, 0) => '.',
+            (2, _) => '#',
+            (_, s) => std::char::from_digit(u32::from(s), 36).unwrap_or('?'),
+        }
+    }
+
+    /// Colour for a state, from dark for 0 to light for the last state.
+    fn colour(&self, state: State) -> [u8; 3] {
+        let level = u32::from(state) * 255 / u32::from(self.states - 1);
+        let level = level as u8;
+
+        [level, level / 2 + 64, 255 - level / 2]
+    }
+
+    /// Write the diagram as a binary PPM image, every cell `scale` pixels wide.
+    pub fn write_ppm<W: Write>(&self, scale: usize, out: &mut W) -> io::Result<()> {
+        let width = (self.to - self.from + 1) as usize * scale;
+        let height = self.rows.len() * scale;
+
+        write!(out, "P6\n{} {}\n255\n", width, height)?;
+
+        for row in self.rows {
+            let line: Vec<u8> = (self.from..=self.to)
+                .flat_map(|p| std::iter::repeat_n(self.colour(row.get(p)), scale))
+                .flatten()
+                .collect();
+
+            for _ in 0..scale {
+                out.write_all(&line)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Diagram<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for row in self.rows {
+            for p in self.from..=self.to {
+                write!(f, "{}", self.symbol(row.get(p)))?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Cell symbols: `.` and `#` for 0 and 1, or digits (and letters) for any
+/// state.
+fn parse_state(c: char) -> Result<State, String> {
+    match c {
+        '.' => Ok(0),
+        '#' => Ok(1),
+        _ => c
+            .to_digit(36)
+            .map(|d| d as State)
+            .ok_or(format!("Invalid cell {}", c)),
+    }
+}
+
+fn parse_cells(s: &str) -> Result<Vec<State>, String> {
+    s.chars().map(parse_state).collect()
+}
+
+/// An automaton with its starting row. Either the day 12 format, with one
+/// `pattern => cell` line per rule, or a single `rule N [states K] [width W]`
+/// line after the initial state.
+pub struct Spec {
+    pub automaton: Automaton,
+    pub row: Row,
+}
+
+impl FromStr for Spec {
+    type Err = String;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut lines = input.lines().filter(|l| !l.trim().is_empty());
+
+        let initial = lines
+            .next()
+            .and_then(|l| l.split_whitespace().next_back())
+            .ok_or("Missing initial state")?;
+        let cells = parse_cells(initial)?;
+
+        let lines: Vec<&str> = lines.collect();
+
+        let automaton = match lines
+            .first()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        {
+            Some(words) if words.first() == Some(&"rule") => {
+                let number = |key: &str, default: u128| -> Result<u128, String> {
+                    match words.iter().position(|w| *w == key) {
+                        Some(i) => words
+                            .get(i + 1)
+                            .and_then(|n| n.parse().ok())
+                            .ok_or(format!("Invalid {} in {}", key, lines[0])),
+                        None => Ok(default),
+                    }
+                };
+
+                let states = number("states", 2)?;
+                let width = number("width", 3)?;
+                if states > u128::from(State::MAX) || width > 64 {
+                    return Err(format!("Unsupported automaton {}", lines[0]));
+                }
+
+                Automaton::wolfram(number("rule", 0)?, states as u8, width as usize)?
+            }
+            _ => {
+                let mut rules = Vec::new();
+                for l in &lines {
+                    let mut columns = l.split("=>").map(str::trim);
+                    let pattern = parse_cells(columns.next().unwrap_or(""))?;
+                    let next = parse_cells(columns.next().ok_or(format!("Invalid rule {}", l))?)?;
+
+                    match next[..] {
+                        [next] => rules.push((pattern, next)),
+                        _ => return Err(format!("Invalid rule {}", l)),
+                    }
+                }
+
+                let width = rules.first().map_or(5, |(pattern, _)| pattern.len());
+                let states = rules
+                    .iter()
+                    .flat_map(|(pattern, next)| pattern.iter().chain(Some(next)))
+                    .chain(&cells)
+                    .max()
+                    .map_or(2, |&s| (s + 1).max(2));
+
+                let mut automaton = Automaton::new(states, width)?;
+                for (pattern, next) in rules {
+                    automaton.set(&pattern, next)?;
+                }
+
+                automaton
+            }
+        };
+
+        if let Some(&s) = cells.iter().find(|&&s| s >= automaton.states) {
+            return Err(format!(
+                "Initial state {} out of range 0..{}",
+                s, automaton.states
+            ));
+        }
+
+        Ok(Spec {
+            automaton,
+            row: Row::new(cells),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(spec: &str, generations: usize) -> String {
+        let spec: Spec = spec.parse().expect("Valid spec");
+        let rows = spec.automaton.run(&spec.row, generations);
+
+        Diagram::new(&rows, spec.automaton.states()).to_string()
+    }
+
+    #[test]
+    fn rule_90() {
+        assert_eq!(
+            "...#...
+..#.#..
+.#...#.
+#.#.#.#
+",
+            render("initial state: #\n\nrule 90", 3)
+        );
+    }
+
+    #[test]
+    fn rule_110() {
+        assert_eq!(
+            "....#
+...##
+..###
+.##.#
+#####
+",
+            render("initial state: #\n\nrule 110", 4)
+        );
+    }
+
+    #[test]
+    fn alternating_background() {
+        // Rule 1: a cell only lives if its whole neighbourhood is dead.
+        let spec: Spec = "initial state: #\n\nrule 1".parse().unwrap();
+        let rows = spec.automaton.run(&spec.row, 2);
+
+        assert_eq!(0, rows[0].background);
+        assert_eq!(1, rows[1].background);
+        assert_eq!(Some((-1, 1)), rows[1].bounds());
+        assert_eq!(0, rows[2].background);
+    }
+
+    #[test]
+    fn three_states() {
+        // Totalistic-ish: next state is the sum of the neighbourhood mod 3.
+        let mut automaton = Automaton::new(3, 3).unwrap();
+        for a in 0..3 {
+            for b in 0..3 {
+                for c in 0..3 {
+                    automaton.set(&[a, b, c], (a + b + c) % 3).unwrap();
+                }
+            }
+        }
+
+        let rows = automaton.run(&Row::new(vec![1]), 2);
+        assert_eq!("00100\n01110\n12021\n", Diagram::new(&rows, 3).to_string());
+    }
+
+    #[test]
+    fn wolfram_multi_state() {
+        // 3 states, width 3: 27 neighbourhoods. Rule 3^26 only maps 222 to 1.
+        let automaton = Automaton::wolfram(3u128.pow(26), 3, 3).unwrap();
+        assert_eq!(1, automaton.table[26]);
+        assert!(automaton.table[..26].iter().all(|&s| s == 0));
+
+        assert!(Automaton::wolfram(256, 2, 3).is_err());
+        assert!(Automaton::new(2, 4).is_err());
+        assert!(Automaton::new(2, 64).is_err());
+    }
+
+    #[test]
+    fn ppm() {
+        let spec: Spec = "initial state: #\n\nrule 90".parse().unwrap();
+        let rows = spec.automaton.run(&spec.row, 3);
+
+        let mut out = Vec::new();
+        Diagram::new(&rows, 2).write_ppm(2, &mut out).unwrap();
+
+        let header = b"P6\n14 8\n255\n";
+        assert_eq!(header, &out[..header.len()]);
+        assert_eq!(header.len() + 14 * 8 * 3, out.len());
+    }
+
+    #[test]
+    fn invalid_specs() {
+        assert!("initial state: #\n\n### => ##".parse::<Spec>().is_err());
+        assert!("initial state: #\n\n#### => #".parse::<Spec>().is_err());
+        assert!("initial state: #!#\n\nrule 30".parse::<Spec>().is_err());
+        assert!("initial state: 3\n\nrule 30".parse::<Spec>().is_err());
+        assert!("".parse::<Spec>().is_err());
+    }
+}
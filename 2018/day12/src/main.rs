@@ -1,9 +1,11 @@
-use std::io::{stdin, Read};
+mod automaton;
+
+use automaton::{Diagram, Spec};
+use std::fs::File;
+use std::io::{stdin, BufWriter, Read};
+use std::iter::FromIterator;
+use std::path::PathBuf;
 use std::str::FromStr;
-use std::{
-    collections::{HashMap, VecDeque},
-    iter::Iterator,
-};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 enum Pot {
@@ -18,140 +20,694 @@ impl Pot {
             _ => Pot::Empty,
         }
     }
+
+    /// Like `from_u8`, but only accepting `#` and `.`.
+    fn parse(b: u8) -> Result<Self, String> {
+        match b {
+            b'#' | b'.' => Ok(Pot::from_u8(b)),
+            _ => Err(format!("Invalid pot {}", b as char)),
+        }
+    }
 }
 
-#[derive(Debug)]
+/// A row of pots, one bit per pot with plants set.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+struct Pots {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Pots {
+    fn with_len(len: usize) -> Self {
+        Pots {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn has_plant(&self, i: usize) -> bool {
+        i < self.len && self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    /// Word `w` with a bit set for every pot that differs from `background`.
+    fn differing(&self, w: usize, background: Pot) -> u64 {
+        let word = match background {
+            Pot::Empty => self.words[w],
+            Pot::Plant => !self.words[w],
+        };
+
+        let end = self.len - w * 64;
+        if end < 64 {
+            word & ((1 << end) - 1)
+        } else {
+            word
+        }
+    }
+
+    fn set_plant(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    /// Indexes of every plant, in order.
+    fn plants(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+
+    fn first_not(&self, background: Pot) -> Option<usize> {
+        (0..self.words.len())
+            .map(|w| (w, self.differing(w, background)))
+            .find(|&(_, word)| word != 0)
+            .map(|(w, word)| w * 64 + word.trailing_zeros() as usize)
+    }
+
+    fn last_not(&self, background: Pot) -> Option<usize> {
+        (0..self.words.len())
+            .rev()
+            .map(|w| (w, self.differing(w, background)))
+            .find(|&(_, word)| word != 0)
+            .map(|(w, word)| w * 64 + 63 - word.leading_zeros() as usize)
+    }
+
+    /// Copy of the pots in `start..end`.
+    fn range(&self, start: usize, end: usize) -> Self {
+        let mut pots = Pots::with_len(end - start);
+
+        for (k, word) in pots.words.iter_mut().enumerate() {
+            let offset = start + k * 64;
+            let (w, bit) = (offset / 64, offset % 64);
+
+            *word = self.words[w] >> bit;
+            if bit > 0 && w + 1 < self.words.len() {
+                *word |= self.words[w + 1] << (64 - bit);
+            }
+        }
+
+        // Keep bits past the end cleared, so equal rows compare equal.
+        if !pots.len.is_multiple_of(64) {
+            if let Some(last) = pots.words.last_mut() {
+                *last &= (1 << (pots.len % 64)) - 1;
+            }
+        }
+
+        pots
+    }
+}
+
+impl FromIterator<Pot> for Pots {
+    fn from_iter<I: IntoIterator<Item = Pot>>(iter: I) -> Self {
+        let pots: Vec<Pot> = iter.into_iter().collect();
+        let mut row = Pots::with_len(pots.len());
+
+        for (i, pot) in pots.into_iter().enumerate() {
+            if pot == Pot::Plant {
+                row.set_plant(i);
+            }
+        }
+
+        row
+    }
+}
+
+/// Neighbourhood of five pots packed into the low bits of an index, the
+/// leftmost pot in the highest bit.
+fn pattern_index(pattern: &[Pot]) -> usize {
+    pattern
+        .iter()
+        .fold(0, |index, &pot| index << 1 | (pot == Pot::Plant) as usize)
+}
+
+fn pattern_string(index: usize) -> String {
+    (0..5)
+        .rev()
+        .map(|bit| if index >> bit & 1 == 1 { '#' } else { '.' })
+        .collect()
+}
+
+/// Outcome for every one of the 32 possible neighbourhoods.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Rules {
+    /// Bit `i` is set if the neighbourhood with index `i` grows a plant.
+    plants: u32,
+    /// Bit `i` is set if the input had a rule for neighbourhood `i`.
+    defined: u32,
+    /// Bit `i` is set if the input repeated the rule for neighbourhood `i`.
+    duplicated: u32,
+}
+
+impl Rules {
+    /// Add a rule. A second rule for the same pattern must agree with the
+    /// first one.
+    fn insert(&mut self, pattern: &[Pot], result: Pot) -> Result<(), String> {
+        if pattern.len() != 5 {
+            let text: String = pattern
+                .iter()
+                .map(|&pot| if pot == Pot::Plant { '#' } else { '.' })
+                .collect();
+
+            return Err(format!(
+                "Pattern {} is {} pots, expected 5",
+                text,
+                pattern.len()
+            ));
+        }
+
+        let index = pattern_index(pattern);
+        let bit = 1 << index;
+
+        if self.defined & bit != 0 {
+            if self.grows(index) != (result == Pot::Plant) {
+                return Err(format!("Conflicting rules for {}", pattern_string(index)));
+            }
+
+            self.duplicated |= bit;
+        }
+
+        self.defined |= bit;
+        if result == Pot::Plant {
+            self.plants |= bit;
+        }
+
+        Ok(())
+    }
+
+    fn grows(&self, index: usize) -> bool {
+        self.plants >> index & 1 == 1
+    }
+
+    /// What a pot far away from every plant turns into, given that all of
+    /// its neighbours are `background`.
+    fn next_background(&self, background: Pot) -> Pot {
+        let index = match background {
+            Pot::Empty => 0,
+            Pot::Plant => 0b11111,
+        };
+
+        if self.grows(index) {
+            Pot::Plant
+        } else {
+            Pot::Empty
+        }
+    }
+
+    /// Things that are not wrong, but probably not what was intended.
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for index in 0..32 {
+            if self.duplicated >> index & 1 == 1 {
+                warnings.push(format!("Duplicate rule for {}", pattern_string(index)));
+            }
+        }
+
+        let missing: Vec<String> = (0..32)
+            .filter(|index| self.defined >> index & 1 == 0)
+            .map(pattern_string)
+            .collect();
+
+        if !missing.is_empty() {
+            warnings.push(format!(
+                "No rule for {} pattern(s), assuming they leave the pot empty: {}",
+                missing.len(),
+                missing.join(" ")
+            ));
+        }
+
+        if self.grows(0) {
+            warnings.push(match self.next_background(Pot::Plant) {
+                Pot::Plant => "..... => # fills the whole row with plants".to_string(),
+                Pot::Empty => "..... => # and ##### => . make the whole row alternate".to_string(),
+            });
+        }
+
+        warnings
+    }
+}
+
+#[derive(Clone, Debug)]
 struct Plants {
-    pots: Vec<Pot>,
-    rules: HashMap<Vec<Pot>, Pot>,
-    zero_index: u64,
+    pots: Pots,
+    rules: Rules,
+    /// Index in `pots` of pot number 0. Negative when pot 0 lies to the left
+    /// of the first stored pot.
+    zero_index: i64,
+    /// What every pot outside of `pots` holds. Only ever a plant when
+    /// `..... => #`.
+    background: Pot,
+}
+
+/// Most generations `Plants::evolve` looks through for a repeating pattern
+/// before it falls back to stepping.
+const CYCLE_SEARCH_LIMIT: u64 = 1_000;
+
+/// Most generations `Plants::evolve` steps through one at a time. A pattern
+/// that keeps changing grows by up to four pots a generation, so stepping
+/// takes time quadratic in the number of generations.
+const STEP_LIMIT: u64 = 1_000_000;
+
+/// A pattern that repeats itself, possibly shifted along the row.
+#[derive(Debug, PartialEq)]
+struct Cycle {
+    /// First generation of the repeating pattern.
+    start: u64,
+    /// Generations between two repetitions.
+    period: u64,
+    /// Pots every plant has moved after one period.
+    shift: i64,
 }
 
 impl Plants {
-    fn evolve(&self, generations: u64) -> Self {
-        let mut pots = VecDeque::from(self.pots.clone());
-        let mut zero_index = self.zero_index;
+    /// State after `generations`. A repeating pattern is projected forward,
+    /// anything else is stepped through unless that would take too long.
+    fn evolve(&self, generations: u64) -> Result<Self, String> {
+        match self.find_cycle(generations.min(CYCLE_SEARCH_LIMIT)) {
+            Some(cycle) => Ok(cycle.project(self, generations)),
+            None if generations <= STEP_LIMIT => Ok(self.evolve_steps(generations)),
+            None => Err(format!(
+                "No repeating pattern within {} gens, and {} gens are too many to step through",
+                CYCLE_SEARCH_LIMIT, generations
+            )),
+        }
+    }
 
-        for _ in 0..generations {
-            // Make some space
-            while pots.iter().take(5).any(|p| *p == Pot::Plant) {
-                pots.push_front(Pot::Empty);
-                zero_index += 1;
+    /// Evolve one generation at a time.
+    fn evolve_steps(&self, generations: u64) -> Self {
+        (0..generations).fold(self.normalised(), |plants, _| plants.step())
+    }
+
+    /// Find the first repetition of the normalised pot pattern, stepping no
+    /// more than `limit` generations ahead. Uses Brent's algorithm, so only
+    /// two rows are kept however long the search.
+    fn find_cycle(&self, limit: u64) -> Option<Cycle> {
+        // Find the period: the tortoise waits at each power of two for the
+        // hare to come round to it.
+        let mut tortoise = self.normalised();
+        let mut hare = tortoise.step();
+        let mut steps = 1;
+        let (mut power, mut period) = (1, 1);
+
+        while !hare.same_pattern(&tortoise) {
+            if steps >= limit {
+                return None;
             }
 
-            while pots.iter().rev().take(5).any(|p| *p == Pot::Plant) {
-                pots.push_back(Pot::Empty);
+            if power == period {
+                tortoise = hare.clone();
+                power *= 2;
+                period = 0;
             }
 
-            let old = pots.clone();
+            hare = hare.step();
+            steps += 1;
+            period += 1;
+        }
 
-            for (i, pot) in pots.iter_mut().enumerate().skip(2) {
-                let slice: Vec<Pot> = old.iter().skip(i - 2).take(5).copied().collect();
+        // Find the start: with the hare one period ahead, both meet at the
+        // first generation of the repeating pattern.
+        let mut tortoise = self.normalised();
+        let mut hare = tortoise.evolve_steps(period);
+        let mut start = 0;
 
-                *pot = if let Some(Pot::Plant) = self.rules.get(&slice) {
-                    Pot::Plant
-                } else {
-                    Pot::Empty
-                };
+        while !hare.same_pattern(&tortoise) {
+            tortoise = tortoise.step();
+            hare = hare.step();
+            start += 1;
+        }
+
+        Some(Cycle {
+            start,
+            period,
+            shift: tortoise.zero_index - hare.zero_index,
+        })
+    }
+
+    /// Whether both hold the same normalised pattern, wherever it is.
+    fn same_pattern(&self, other: &Plants) -> bool {
+        self.pots == other.pots && self.background == other.background
+    }
+
+    /// Advance a single generation. The result is normalised.
+    fn step(&self) -> Self {
+        // Only pots within two of a pot that differs from the background can
+        // change differently from it, so the new row starts two pots to the
+        // left and ends two pots to the right.
+        let len = self.pots.len() + 4;
+        let mut pots = Pots::with_len(len);
+
+        let outside = self.background == Pot::Plant;
+
+        // Slide a five pot window along the row. Pot `j` of the new row is
+        // centered on old pot `j - 2`, so the window takes in old pot `j`.
+        let mut window = if outside { 0b1111 } else { 0 };
+        for j in 0..len {
+            let plant = if j < self.pots.len() {
+                self.pots.has_plant(j)
+            } else {
+                outside
+            };
+
+            window = (window << 1 | plant as usize) & 0b11111;
+
+            if self.rules.grows(window) {
+                pots.set_plant(j);
             }
         }
 
         Plants {
-            pots: Vec::from(pots),
-            rules: self.rules.clone(),
+            pots,
+            rules: self.rules,
+            zero_index: self.zero_index + 2,
+            background: self.rules.next_background(self.background),
+        }
+        .normalised()
+    }
+
+    /// Strip background pots from both ends, so that equal patterns compare
+    /// equal wherever they are on the row.
+    fn normalised(&self) -> Self {
+        let first = self.pots.first_not(self.background);
+        let last = self.pots.last_not(self.background);
+
+        let (pots, zero_index) = match (first, last) {
+            (Some(start), Some(end)) => (
+                self.pots.range(start, end + 1),
+                self.zero_index - start as i64,
+            ),
+            _ => (Pots::default(), 0),
+        };
+
+        Plants {
+            pots,
+            rules: self.rules,
             zero_index,
+            background: self.background,
         }
     }
 
+    #[cfg(test)]
     fn sum(&self) -> i64 {
-        self.pots
-            .iter()
-            .enumerate()
-            .filter(|(_, p)| **p == Pot::Plant)
-            .map(|x| x.0 as i64 - self.zero_index as i64)
-            .sum()
+        self.try_sum().expect("Infinitely many plants")
+    }
+
+    /// Sum of the pots with a plant after `generations`. Rules that fill
+    /// the whole row are recognised without evolving, the pattern in the
+    /// middle might keep changing forever but the sum is infinite anyway.
+    fn sum_after(&self, generations: u64) -> Result<Option<i64>, String> {
+        let fills = self.rules.next_background(Pot::Empty) == Pot::Plant
+            && self.rules.next_background(Pot::Plant) == Pot::Plant;
+
+        if generations > 0 && fills {
+            return Ok(None);
+        }
+
+        Ok(self.evolve(generations)?.try_sum())
+    }
+
+    /// Sum of the numbers of all pots with a plant, unless the plants go on
+    /// forever.
+    fn try_sum(&self) -> Option<i64> {
+        match self.background {
+            Pot::Empty => Some(self.pots.plants().map(|i| i as i64 - self.zero_index).sum()),
+            Pot::Plant => None,
+        }
+    }
+}
+
+impl Cycle {
+    /// State of `plants` after `generations`. Only the generations up to the
+    /// matching point of the first period are evolved, the remaining whole
+    /// periods just move the pattern.
+    fn project(&self, plants: &Plants, generations: u64) -> Plants {
+        let cycles = (generations - self.start) / self.period;
+        let offset = (generations - self.start) % self.period;
+
+        let mut plants = plants.evolve_steps(self.start + offset);
+        plants.zero_index -= self.shift * cycles as i64;
+        plants
     }
 }
 
 impl FromStr for Plants {
-    type Err = ();
+    type Err = String;
     fn from_str(input: &str) -> Result<Self, Self::Err> {
         let mut lines = input.lines();
 
+        let pots = lines
+            .next()
+            .and_then(|l| l.split_whitespace().next_back())
+            .ok_or("Missing initial state")?
+            .bytes()
+            .map(Pot::parse)
+            .collect::<Result<_, _>>()?;
+
+        let mut rules = Rules::default();
+
+        for l in lines.filter(|l| l.chars().any(|c| !c.is_whitespace())) {
+            let columns: Vec<&str> = l.split_whitespace().collect();
+
+            let (pattern, result) = match columns[..] {
+                [pattern, "=>", result] if result.len() == 1 => (pattern, result),
+                _ => return Err(format!("Invalid rule {}", l)),
+            };
+
+            let pattern: Vec<Pot> = pattern.bytes().map(Pot::parse).collect::<Result<_, _>>()?;
+            let result = Pot::parse(result.as_bytes()[0])?;
+
+            rules.insert(&pattern, result)?;
+        }
+
+        Ok(Plants {
+            pots,
+            rules,
+            zero_index: 0,
+            background: Pot::Empty,
+        })
+    }
+}
+
+/// The first implementation, looking up a freshly allocated slice in a
+/// `HashMap` for every pot. Kept to test and benchmark against.
+#[cfg(test)]
+mod reference {
+    use super::Pot;
+    use std::collections::{HashMap, VecDeque};
+
+    pub fn parse(input: &str) -> (Vec<Pot>, HashMap<Vec<Pot>, Pot>) {
+        let mut lines = input.lines();
+
         let pots = lines
             .next()
             .unwrap()
             .split_whitespace()
-            .last()
+            .next_back()
             .unwrap()
             .bytes()
             .map(Pot::from_u8)
             .collect();
 
-        let rules: HashMap<Vec<Pot>, Pot> = lines
+        let rules = lines
             .filter(|l| l.chars().any(|c| !c.is_whitespace()))
             .map(|l| {
                 let mut columns = l.split_whitespace();
                 let pattern = columns.next().unwrap().bytes().map(Pot::from_u8).collect();
                 let result = columns
-                    .last()
+                    .next_back()
                     .unwrap()
                     .bytes()
                     .map(Pot::from_u8)
-                    .last()
+                    .next_back()
                     .unwrap();
 
                 (pattern, result)
             })
             .collect();
 
-        Ok(Plants {
-            pots,
-            rules,
-            zero_index: 0,
-        })
+        (pots, rules)
+    }
+
+    pub fn evolve_sum(pots: &[Pot], rules: &HashMap<Vec<Pot>, Pot>, generations: u64) -> i64 {
+        let mut pots = VecDeque::from(pots.to_vec());
+        let mut zero_index = 0;
+
+        for _ in 0..generations {
+            // Make some space
+            while pots.iter().take(5).any(|p| *p == Pot::Plant) {
+                pots.push_front(Pot::Empty);
+                zero_index += 1;
+            }
+
+            while pots.iter().rev().take(5).any(|p| *p == Pot::Plant) {
+                pots.push_back(Pot::Empty);
+            }
+
+            let old = pots.clone();
+
+            for (i, pot) in pots.iter_mut().enumerate().skip(2) {
+                let slice: Vec<Pot> = old.iter().skip(i - 2).take(5).copied().collect();
+
+                *pot = if let Some(Pot::Plant) = rules.get(&slice) {
+                    Pot::Plant
+                } else {
+                    Pot::Empty
+                };
+            }
+        }
+
+        pots.iter()
+            .enumerate()
+            .filter(|(_, p)| **p == Pot::Plant)
+            .map(|x| x.0 as i64 - zero_index)
+            .sum()
+    }
+}
+
+/// What to do with the input.
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Puzzle,
+    Diagram,
+    Image(PathBuf),
+}
+
+/// Command line flags. `--diagram` prints the space-time diagram and
+/// `--image FILE.ppm` writes it as a PPM image instead. Both also accept
+/// `rule 110` style input with any number of states. `--generations N` sets
+/// how many generations they run for.
+#[derive(Debug, PartialEq)]
+struct Options {
+    mode: Mode,
+    generations: Option<usize>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            mode: Mode::Puzzle,
+            generations: None,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+
+            let mode = match arg.as_str() {
+                "--diagram" => Mode::Diagram,
+                "--image" => {
+                    let path = PathBuf::from(value()?);
+                    match path.extension().and_then(|e| e.to_str()) {
+                        Some(e) if e.eq_ignore_ascii_case("ppm") => Mode::Image(path),
+                        _ => return Err(format!("{} is not a .ppm file", path.display())),
+                    }
+                }
+                "--generations" => {
+                    let value = value()?;
+                    options.generations = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid number of generations {}", value))?,
+                    );
+                    continue;
+                }
+                _ => return Err(format!("Unknown argument {}", arg)),
+            };
+
+            if options.mode != Mode::Puzzle {
+                return Err(format!("{} cannot be combined with another mode", arg));
+            }
+            options.mode = mode;
+        }
+
+        if options.mode == Mode::Puzzle && options.generations.is_some() {
+            return Err("--generations needs --diagram or --image".to_string());
+        }
+
+        Ok(options)
     }
 }
 
 fn main() {
+    let fail = |e: String| -> ! {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    };
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| fail(e));
+
     let mut input = String::new();
     stdin()
         .read_to_string(&mut input)
         .expect("Failed to read stdin");
 
-    let plants: Plants = input.parse().unwrap();
+    let diagram = |default| {
+        let spec: Spec = input.parse().unwrap_or_else(|e| fail(e));
+        let rows = spec
+            .automaton
+            .run(&spec.row, options.generations.unwrap_or(default));
 
-    println!(
-        "Part 1. Pots with plant in gen 20: {}",
-        plants.evolve(20).sum()
-    );
+        (rows, spec.automaton.states())
+    };
 
-    let mut factor = 0;
-    let mut last_two_digits = 0;
-    for i in (200..=500).step_by(100) {
-        let sum = plants.evolve(i).sum();
-        last_two_digits = sum % 100;
-        factor = (sum - last_two_digits) / i as i64;
-        println!(
-            "Gen {0:4} sum: {1:5} = {0:4} * {2:3} + {3:2}",
-            i,
-            plants.evolve(i).sum(),
-            factor,
-            last_two_digits
-        );
+    match &options.mode {
+        Mode::Puzzle => {}
+        Mode::Diagram => {
+            let (rows, states) = diagram(20);
+            print!("{}", Diagram::new(&rows, states));
+            return;
+        }
+        Mode::Image(path) => {
+            let (rows, states) = diagram(100);
+            File::create(path)
+                .map(BufWriter::new)
+                .and_then(|mut file| Diagram::new(&rows, states).write_ppm(4, &mut file))
+                .unwrap_or_else(|e| fail(format!("Failed to write {}: {}", path.display(), e)));
+            return;
+        }
+    }
+
+    let plants: Plants = input.parse().unwrap_or_else(|e| fail(e));
+
+    for warning in plants.rules.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let describe = |generations| match plants.sum_after(generations) {
+        Ok(Some(sum)) => sum.to_string(),
+        Ok(None) => "infinite".to_string(),
+        Err(e) => format!("unknown. {}", e),
+    };
+
+    println!("Part 1. Pots with plant in gen 20: {}", describe(20));
+
+    match plants.find_cycle(CYCLE_SEARCH_LIMIT) {
+        Some(cycle) => println!(
+            "Pattern repeats from gen {} every {} gen(s), shifted {} pot(s)",
+            cycle.start, cycle.period, cycle.shift
+        ),
+        None => println!("No repeating pattern within {} gens", CYCLE_SEARCH_LIMIT),
     }
 
     println!(
-        "Part 2. Guess: 50000000000 * {} + {} = {}",
-        factor,
-        last_two_digits,
-        50_000_000_000 * factor + last_two_digits
+        "Part 2. Pots with plant in gen 50000000000: {}",
+        describe(50_000_000_000)
     );
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
 
     fn data() -> String {
         "initial state: #..#.#..##......###...###
@@ -179,11 +735,25 @@ mod tests {
 
         let to_pots = |s: &str| s.bytes().map(Pot::from_u8).collect::<Vec<Pot>>();
 
-        assert_eq!(to_pots("#..#.#..##......###...###"), plants.pots);
-        assert_eq!(14, plants.rules.len());
+        assert_eq!(
+            to_pots("#..#.#..##......###...###")
+                .into_iter()
+                .collect::<Pots>(),
+            plants.pots
+        );
+        assert_eq!(14, plants.rules.defined.count_ones());
         assert_eq!(0, plants.zero_index);
 
-        let rule = |chars: &str| *plants.rules.get(&to_pots(chars)).expect("Missing rule");
+        let rule = |chars: &str| {
+            let index = pattern_index(&to_pots(chars));
+            assert_eq!(1, plants.rules.defined >> index & 1, "Missing rule");
+
+            if plants.rules.grows(index) {
+                Pot::Plant
+            } else {
+                Pot::Empty
+            }
+        };
 
         assert_eq!(rule("...##"), Pot::Plant);
         assert_eq!(rule("..#.."), Pot::Plant);
@@ -210,18 +780,307 @@ mod tests {
         ]
         .to_vec();
 
-        let p = plants.evolve(20);
+        let p = plants.evolve(20).unwrap();
 
-        let indexes: Vec<i64> = p
-            .pots
-            .iter()
-            .enumerate()
-            .filter(|(_, p)| **p == Pot::Plant)
-            .map(|x| x.0 as i64 - p.zero_index as i64)
-            .collect();
+        let indexes: Vec<i64> = p.pots.plants().map(|i| i as i64 - p.zero_index).collect();
 
         assert_eq!(expected_indexes, indexes);
 
         assert_eq!(325, p.sum());
     }
+
+    #[test]
+    fn cycle() {
+        let plants: Plants = data().parse().expect("Failed to parse test data");
+        let cycle = plants.find_cycle(1000).expect("Example should repeat");
+
+        assert_eq!(1, cycle.period);
+        assert_eq!(1, cycle.shift);
+
+        let later = plants.evolve_steps(cycle.start + 5);
+        let start = plants.evolve_steps(cycle.start);
+        assert_eq!(start.pots, later.pots);
+        assert_eq!(start.zero_index - 5, later.zero_index);
+    }
+
+    #[test]
+    fn evolve_matches_naive() {
+        let plants: Plants = data().parse().expect("Failed to parse test data");
+
+        for &generations in &[0, 1, 20, 86, 87, 88, 150, 500] {
+            assert_eq!(
+                plants.evolve_steps(generations).sum(),
+                plants.evolve(generations).unwrap().sum(),
+                "Gen {}",
+                generations
+            );
+        }
+    }
+
+    #[test]
+    fn oscillating_cycle() {
+        // A single plant that alternates between one and three plants.
+        let plants: Plants = "initial state: ..#..
+
+..#.. => #
+.#... => #
+...#. => #
+.###. => #"
+            .parse()
+            .expect("Failed to parse test data");
+
+        let cycle = plants.find_cycle(10).expect("Should oscillate");
+        assert_eq!(
+            Cycle {
+                start: 0,
+                period: 2,
+                shift: 0
+            },
+            cycle
+        );
+
+        assert_eq!(2, plants.evolve(1_000_000_001).unwrap().sum() / 3);
+        assert_eq!(2, plants.evolve(1_000_000_000).unwrap().sum());
+    }
+
+    #[test]
+    fn growing_pattern() {
+        // Every pot near a plant grows one, so the row only ever gets longer.
+        let lines: Vec<String> = (1..32)
+            .map(|index| format!("{} => #", pattern_string(index)))
+            .collect();
+        let plants: Plants = rules(&lines.join("\n")).parse().unwrap();
+
+        // Pots -200 to 203 all have plants.
+        assert_eq!(Ok(Some(201 + 202 + 203)), plants.sum_after(100));
+
+        // Past the cycle search, the pots are stepped through instead.
+        let generations = CYCLE_SEARCH_LIMIT + 500;
+        let (first, last) = (-2 * generations as i64, 3 + 2 * generations as i64);
+        assert_eq!(
+            Ok(Some((first..=last).sum())),
+            plants.sum_after(generations)
+        );
+
+        assert!(plants.sum_after(STEP_LIMIT + 1).is_err());
+    }
+
+    #[test]
+    fn dying_pattern() {
+        let plants: Plants = "initial state: #.#
+
+#.#.. => ."
+            .parse()
+            .expect("Failed to parse test data");
+
+        assert_eq!(0, plants.evolve(50_000_000_000).unwrap().sum());
+        assert_eq!(None, plants.evolve(1).unwrap().pots.first_not(Pot::Empty));
+    }
+
+    #[test]
+    fn pots_range() {
+        let row: Pots = (0..200)
+            .map(|i| if i % 3 == 0 { Pot::Plant } else { Pot::Empty })
+            .collect();
+
+        let range = row.range(63, 140);
+        assert_eq!(77, range.len());
+        assert_eq!(Some(0), range.first_not(Pot::Empty));
+        assert_eq!(Some(75), range.last_not(Pot::Empty));
+        assert_eq!(Some(1), range.first_not(Pot::Plant));
+        assert_eq!(Some(76), range.last_not(Pot::Plant));
+        assert!(range.plants().all(|i| (i + 63) % 3 == 0));
+        assert_eq!(26, range.plants().count());
+    }
+
+    #[test]
+    fn matches_reference() {
+        let plants: Plants = data().parse().expect("Failed to parse test data");
+        let (pots, rules) = reference::parse(&data());
+
+        for &generations in &[0, 1, 20, 100] {
+            assert_eq!(
+                reference::evolve_sum(&pots, &rules, generations),
+                plants.evolve_steps(generations).sum()
+            );
+        }
+    }
+
+    /// Time stepping through the example with both the bit packed rows and
+    /// the reference implementation. Run with
+    /// `cargo test --release bench -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench() {
+        let generations = 5_000;
+        let plants: Plants = data().parse().expect("Failed to parse test data");
+
+        let start = Instant::now();
+        let sum = plants.evolve_steps(generations).sum();
+        let packed = start.elapsed();
+
+        let (pots, rules) = reference::parse(&data());
+        let start = Instant::now();
+        let reference_sum = reference::evolve_sum(&pots, &rules, generations);
+        let hashed = start.elapsed();
+
+        assert_eq!(reference_sum, sum, "Implementations disagree");
+
+        println!("Bench {} gens, sum {}", generations, sum);
+        println!("  bit packed: {:?}", packed);
+        println!("  reference:  {:?}", hashed);
+    }
+
+    #[test]
+    fn automaton_matches_plants() {
+        let plants: Plants = data().parse().expect("Failed to parse test data");
+        let spec: Spec = data().parse().expect("Failed to parse test data");
+
+        let rows = spec.automaton.run(&spec.row, 20);
+        let (first, last) = rows[20].bounds().unwrap();
+        let sum: i64 = (first..=last).filter(|&p| rows[20].get(p) == 1).sum();
+
+        assert_eq!(plants.evolve(20).unwrap().sum(), sum);
+    }
+
+    fn rules(lines: &str) -> String {
+        format!("initial state: #..#\n\n{}", lines)
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let parse = |s: &str| s.parse::<Plants>().map(|_| ());
+
+        assert!(parse(&rules("#### => #")).is_err());
+        assert_eq!(
+            Err("Pattern #.#.#. is 6 pots, expected 5".to_string()),
+            parse(&rules("#.#.#. => #")).map(|_| ())
+        );
+        assert!(parse(&rules("#.#.# => ##")).is_err());
+        assert!(parse(&rules("#.#.# #")).is_err());
+        assert!(parse(&rules("#.x.# => #")).is_err());
+        assert!(parse("initial state: #..x").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn duplicate_rules() {
+        let conflict = rules("#.#.# => #\n#.#.# => .");
+        assert_eq!(
+            Err("Conflicting rules for #.#.#".to_string()),
+            conflict.parse::<Plants>().map(|_| ())
+        );
+
+        let plants: Plants = rules("#.#.# => #\n#.#.# => #").parse().unwrap();
+        let warnings = plants.rules.warnings();
+        assert_eq!("Duplicate rule for #.#.#", warnings[0]);
+        assert!(warnings[1].starts_with("No rule for 31 pattern(s)"));
+    }
+
+    #[test]
+    fn complete_rules_have_no_warnings() {
+        let all: Vec<String> = (0..32)
+            .map(|i| {
+                format!(
+                    "{} => {}",
+                    pattern_string(i),
+                    if i == 4 { '#' } else { '.' }
+                )
+            })
+            .collect();
+
+        let plants: Plants = rules(&all.join("\n")).parse().unwrap();
+        assert!(plants.rules.warnings().is_empty());
+
+        // Only ..#.. => #, so a plant stays a plant and nothing else grows.
+        assert_eq!(3, plants.evolve(1_000).unwrap().sum());
+    }
+
+    #[test]
+    fn alternating_background() {
+        // Empty neighbourhoods grow a plant, full ones die.
+        let plants: Plants = rules("..... => #\n##### => .").parse().unwrap();
+        assert!(plants
+            .rules
+            .warnings()
+            .iter()
+            .any(|w| w.contains("alternate")));
+
+        let gen1 = plants.evolve(1).unwrap();
+        assert_eq!(Pot::Plant, gen1.background);
+        assert_eq!(None, gen1.try_sum());
+
+        let gen2 = plants.evolve(2).unwrap();
+        assert_eq!(Pot::Empty, gen2.background);
+        assert_eq!(
+            Some(gen2.sum()),
+            plants.evolve(2_000_000).unwrap().try_sum()
+        );
+        assert_eq!(None, plants.evolve(2_000_001).unwrap().try_sum());
+
+        let cycle = plants.find_cycle(10).expect("Should alternate");
+        assert_eq!(2, cycle.period);
+    }
+
+    #[test]
+    fn alternating_background_matches_automaton() {
+        let input = rules("..... => #\n##### => .\n#.... => #\n...## => #\n.#.#. => #");
+        let plants: Plants = input.parse().unwrap();
+        let spec: Spec = input.parse().unwrap();
+        let rows = spec.automaton.run(&spec.row, 6);
+
+        for (generation, row) in rows.iter().enumerate() {
+            let p = plants.evolve_steps(generation as u64);
+
+            for position in -10..15 {
+                let index = position + p.zero_index;
+                let pot = if index >= 0 && (index as usize) < p.pots.len() {
+                    p.pots.has_plant(index as usize)
+                } else {
+                    p.background == Pot::Plant
+                };
+
+                assert_eq!(
+                    row.get(position) == 1,
+                    pot,
+                    "gen {} pot {}",
+                    generation,
+                    position
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn filling_background() {
+        let plants: Plants = rules("..... => #\n##### => #").parse().unwrap();
+        assert!(plants.rules.warnings().iter().any(|w| w.contains("fills")));
+        assert_eq!(None, plants.evolve(100).unwrap().try_sum());
+        assert_eq!(Ok(None), plants.sum_after(50_000_000_000));
+        assert_eq!(Ok(Some(3)), plants.sum_after(0));
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        let options = |s| Options::from_args(args(s).into_iter());
+
+        assert_eq!(
+            Ok(Options {
+                mode: Mode::Image(PathBuf::from("rule.PPM")),
+                generations: Some(50),
+            }),
+            options("--image rule.PPM --generations 50")
+        );
+        assert_eq!(Mode::Diagram, options("--diagram").unwrap().mode);
+        assert_eq!(Mode::Puzzle, options("").unwrap().mode);
+
+        assert!(options("--image rule.png").is_err());
+        assert!(options("--image").is_err());
+        assert!(options("--diagram --image rule.ppm").is_err());
+        assert!(options("--bench").is_err());
+        assert!(options("--diagram --generations x").is_err());
+        assert!(options("--generations 5").is_err());
+        assert!(options("--verbose").is_err());
+    }
 }
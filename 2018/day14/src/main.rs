@@ -1,145 +1,699 @@
+mod scoreboard;
+
+use scoreboard::{Packing, Scoreboard};
+use std::collections::VecDeque;
+use std::fmt;
 use std::io::stdin;
+use std::str::FromStr;
+
+/// How new recipes are created. The puzzle starts with scores 3 and 7, two
+/// elves and splits sums into decimal digits.
+#[derive(Clone, Debug, PartialEq)]
+struct Config {
+    initial: Vec<u8>,
+    elves: usize,
+    base: u8,
+    /// How scores are stored, packed whenever the base allows by default.
+    packing: Option<Packing>,
+    /// Bytes of scores to keep on the heap before spilling to a file.
+    memory_limit: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            initial: vec![3, 7],
+            elves: 2,
+            base: 10,
+            packing: None,
+            memory_limit: None,
+        }
+    }
+}
+
+impl Config {
+    fn validate(&self) -> Result<(), String> {
+        if !(2..=36).contains(&self.base) {
+            return Err(format!("Base {} is not within 2..=36", self.base));
+        }
+
+        if self.elves == 0 || self.elves > self.initial.len() {
+            return Err(format!(
+                "{} elves need at least as many initial scores, got {}",
+                self.elves,
+                self.initial.len()
+            ));
+        }
 
+        if let Some(s) = self.initial.iter().find(|&&s| s >= self.base) {
+            return Err(format!("Score {} is not a digit in base {}", s, self.base));
+        }
+
+        if self.packing == Some(Packing::Nibbles) && self.base > 16 {
+            return Err(format!("Base {} digits do not fit in a nibble", self.base));
+        }
+
+        Ok(())
+    }
+
+    fn packing(&self) -> Packing {
+        match self.packing {
+            Some(packing) => packing,
+            None if self.base <= 16 => Packing::Nibbles,
+            None => Packing::Bytes,
+        }
+    }
+
+    /// Digits of a needle or score list written in this base.
+    fn parse_digits(&self, s: &str) -> Result<Vec<u8>, String> {
+        s.chars()
+            .map(|c| {
+                c.to_digit(u32::from(self.base))
+                    .map(|d| d as u8)
+                    .ok_or(format!("{} is not a digit in base {}", c, self.base))
+            })
+            .collect()
+    }
+
+    fn digit_char(&self, digit: u8) -> char {
+        std::char::from_digit(u32::from(digit), u32::from(self.base)).unwrap()
+    }
+}
+
+/// The scoreboard as an endless iterator of digits, starting with the
+/// initial scores and creating new recipes as needed.
 struct RecipeScores {
-    pos_1: usize,
-    pos_2: usize,
-    scores: Vec<u8>,
+    elves: Vec<usize>,
+    scores: Scoreboard,
+    base: u8,
+    /// Index of the next score to yield.
+    index: usize,
 }
 
 impl RecipeScores {
-    fn new(scores: Vec<u8>) -> Self {
+    fn new(config: &Config) -> Self {
+        config.validate().unwrap_or_else(|e| panic!("{}", e));
+
+        let mut scores = Scoreboard::new(config.packing(), config.memory_limit);
+        for &score in &config.initial {
+            scores.push(score);
+        }
+
         RecipeScores {
-            pos_1: 0,
-            pos_2: 1,
+            elves: (0..config.elves).collect(),
             scores,
+            base: config.base,
+            index: 0,
+        }
+    }
+
+    fn usage(&self) -> Usage {
+        Usage {
+            scores: self.scores.len(),
+            heap: self.scores.peak_heap(),
+            mapped: self.scores.mapped(),
+            resident: scoreboard::peak_resident(),
+        }
+    }
+
+    /// Every elf combines their current recipe into new ones, one for each
+    /// digit of the sum, and then steps forward.
+    fn create(&mut self) {
+        let base = u32::from(self.base);
+        let mut sum: u32 = self
+            .elves
+            .iter()
+            .map(|&e| u32::from(self.scores.get(e)))
+            .sum();
+
+        let mut digits = vec![(sum % base) as u8];
+        sum /= base;
+        while sum > 0 {
+            digits.push((sum % base) as u8);
+            sum /= base;
+        }
+        for &digit in digits.iter().rev() {
+            self.scores.push(digit);
+        }
+
+        let len = self.scores.len();
+        for elf in self.elves.iter_mut() {
+            *elf = (*elf + 1 + self.scores.get(*elf) as usize) % len;
         }
     }
 }
 
 impl Iterator for RecipeScores {
-    type Item = usize;
+    type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let sum = self.scores[self.pos_1] + self.scores[self.pos_2];
-
-        let len_diff = {
-            if sum > 9 {
-                self.scores.push(1);
-                self.scores.push(sum - 10);
-                2
-            } else {
-                self.scores.push(sum);
-                1
-            }
-        };
+        while self.index >= self.scores.len() {
+            self.create();
+        }
 
-        self.pos_1 += 1 + self.scores[self.pos_1] as usize;
-        self.pos_2 += 1 + self.scores[self.pos_2] as usize;
+        self.index += 1;
+        Some(self.scores.get(self.index - 1))
+    }
+}
+
+/// How much memory the scoreboard took to reach an answer.
+#[derive(Debug)]
+struct Usage {
+    scores: usize,
+    /// Peak bytes of scores on the heap.
+    heap: usize,
+    /// Bytes of scores mapped from a spill file, if the board spilled.
+    mapped: Option<usize>,
+    /// Peak resident memory of the whole process, where known.
+    resident: Option<usize>,
+}
 
-        self.pos_1 %= self.scores.len();
-        self.pos_2 %= self.scores.len();
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
 
-        Some(len_diff)
+        write!(
+            f,
+            "{} scores, {:.1} MiB peak heap",
+            self.scores,
+            mib(self.heap)
+        )?;
+        if let Some(mapped) = self.mapped {
+            write!(f, ", {:.1} MiB mapped", mib(mapped))?;
+        }
+        if let Some(resident) = self.resident {
+            write!(f, ", {:.1} MiB peak resident", mib(resident))?;
+        }
+
+        Ok(())
     }
 }
 
-fn part1(recipe_count: usize) -> String {
-    let mut rc = RecipeScores::new(vec![3, 7]);
+/// A needle found in the digit stream.
+#[derive(Debug, PartialEq)]
+struct Occurrence {
+    /// Which of the needles matched.
+    needle: usize,
+    /// Number of recipes to the left of the match.
+    index: usize,
+}
+
+/// Aho-Corasick automaton over the digits of a base. With a single needle it
+/// behaves like KMP: after a mismatch it falls back to the longest proper
+/// suffix of what has been matched that is also a prefix of a needle, so
+/// overlapping and self-similar needles like `01012` are never missed.
+struct Matcher {
+    /// Next state for every state and digit.
+    transitions: Vec<Vec<usize>>,
+    /// Needles that end in every state, including those ending in a suffix.
+    outputs: Vec<Vec<usize>>,
+    lengths: Vec<usize>,
+}
+
+impl Matcher {
+    fn new(needles: &[Vec<u8>], base: u8) -> Self {
+        let base = base as usize;
+
+        // Trie of all needles, state 0 being the root.
+        let mut goto: Vec<Vec<Option<usize>>> = vec![vec![None; base]];
+        let mut outputs = vec![Vec::new()];
+
+        for (n, needle) in needles.iter().enumerate() {
+            let mut state = 0;
+
+            for &digit in needle {
+                state = match goto[state][digit as usize] {
+                    Some(next) => next,
+                    None => {
+                        goto.push(vec![None; base]);
+                        outputs.push(Vec::new());
+                        goto[state][digit as usize] = Some(goto.len() - 1);
+                        goto.len() - 1
+                    }
+                };
+            }
+
+            outputs[state].push(n);
+        }
+
+        // Breadth first, so that the failure state of every state is known
+        // before its children are visited.
+        let mut transitions = vec![vec![0; base]; goto.len()];
+        let mut fail = vec![0; goto.len()];
+        let mut queue = VecDeque::new();
+
+        for digit in 0..base {
+            if let Some(next) = goto[0][digit] {
+                transitions[0][digit] = next;
+                queue.push_back(next);
+            }
+        }
 
-    while rc.scores.len() < recipe_count + 10 {
-        rc.next();
+        while let Some(state) = queue.pop_front() {
+            let inherited = outputs[fail[state]].clone();
+            outputs[state].extend(inherited);
+
+            for digit in 0..base {
+                match goto[state][digit] {
+                    Some(next) => {
+                        fail[next] = transitions[fail[state]][digit];
+                        transitions[state][digit] = next;
+                        queue.push_back(next);
+                    }
+                    None => transitions[state][digit] = transitions[fail[state]][digit],
+                }
+            }
+        }
+
+        Matcher {
+            transitions,
+            outputs,
+            lengths: needles.iter().map(Vec::len).collect(),
+        }
     }
 
-    rc.scores[recipe_count..]
-        .iter()
+    /// Every occurrence of every needle in `digits`, in the order they end.
+    fn matches<'a, I>(&'a self, digits: I) -> impl Iterator<Item = Occurrence> + 'a
+    where
+        I: Iterator<Item = u8> + 'a,
+    {
+        let mut state = 0;
+
+        digits.enumerate().flat_map(move |(i, digit)| {
+            state = self.transitions[state][digit as usize];
+
+            self.outputs[state].iter().map(move |&needle| Occurrence {
+                needle,
+                index: i + 1 - self.lengths[needle],
+            })
+        })
+    }
+}
+
+fn part1(config: &Config, recipe_count: usize) -> String {
+    RecipeScores::new(config)
+        .skip(recipe_count)
         .take(10)
-        .map(|s| (b'0' + s) as char)
+        .map(|s| config.digit_char(s))
         .collect()
 }
 
-fn part2(needle: &str) -> usize {
-    let scores: Vec<u8> = needle
-        .bytes()
-        .map(|b| b - b'0') // Remove ascii point
-        .collect();
+fn part2(config: &Config, needle: &str) -> Result<(usize, Usage), String> {
+    let needle = config.parse_digits(needle)?;
+    let matcher = Matcher::new(&[needle], config.base);
+
+    let mut scores = RecipeScores::new(config);
+    let first = matcher.matches(scores.by_ref()).next();
+    let index = first.expect("Digit stream is endless").index;
+
+    Ok((index, scores.usage()))
+}
+
+/// Every occurrence of any of `needles` within the first `limit` recipes.
+fn find_all(config: &Config, needles: &[&str], limit: usize) -> Result<Vec<Occurrence>, String> {
+    let needles = needles
+        .iter()
+        .map(|n| match config.parse_digits(n)? {
+            digits if digits.is_empty() => Err("Empty needle".to_string()),
+            digits => Ok(digits),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let mut rc = RecipeScores::new(vec![3, 7]);
-    let mut last_seen = 0;
+    let matcher = Matcher::new(&needles, config.base);
+    let digits = RecipeScores::new(config).take(limit);
 
-    loop {
-        let len_diff = rc.next().unwrap();
+    Ok(matcher.matches(digits).collect())
+}
+
+/// Command line flags. `--scores 3,7`, `--elves 2` and `--base 10` change how
+/// recipes are created, `--find NEEDLE,NEEDLE..` together with `--limit N`
+/// lists every occurrence of the needles within the first N recipes.
+/// `--packing bytes|nibbles` picks how scores are stored and `--max-memory N`
+/// spills them to a memory mapped file once they outgrow N bytes.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    config: Config,
+    find: Option<Vec<String>>,
+    limit: Option<usize>,
+}
 
-        let start = rc.scores.len() - len_diff;
-        for (i, &s) in rc.scores[start..].iter().enumerate() {
-            if last_seen == scores.len() {
-                let processed = len_diff - i;
-                return rc.scores.len() - scores.len() - processed;
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or(format!("Missing value for {}", arg))?;
+            fn number<T: FromStr>(s: &str) -> Result<T, String> {
+                s.parse().map_err(|_| format!("Invalid number {}", s))
             }
 
-            last_seen = {
-                if s == scores[last_seen] {
-                    last_seen + 1
-                } else if s == scores[0] {
-                    1
-                } else {
-                    0
+            match arg.as_str() {
+                "--scores" => {
+                    options.config.initial =
+                        value.split(',').map(number).collect::<Result<_, _>>()?
                 }
-            };
+                "--elves" => options.config.elves = number(&value)?,
+                "--base" => options.config.base = number(&value)?,
+                "--packing" => {
+                    options.config.packing = Some(match value.as_str() {
+                        "bytes" => Packing::Bytes,
+                        "nibbles" => Packing::Nibbles,
+                        _ => return Err(format!("Unknown packing {}", value)),
+                    })
+                }
+                "--max-memory" => options.config.memory_limit = Some(number(&value)?),
+                "--find" => options.find = Some(value.split(',').map(String::from).collect()),
+                "--limit" => options.limit = Some(number(&value)?),
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
         }
+
+        options.config.validate()?;
+        Ok(options)
     }
 }
 
 fn main() {
+    let options = Options::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let config = &options.config;
+
     let mut input = String::new();
     stdin().read_line(&mut input).expect("Failed to read stdin");
 
     let recipe_count = input.trim().parse().expect("Invalid number");
-    println!("Part 1: {}", part1(recipe_count));
+    println!("Part 1: {}", part1(config, recipe_count));
+
+    // The puzzle input is a decimal number, which need not be a valid
+    // sequence of scores in other bases.
+    match part2(config, input.trim()) {
+        Ok((index, usage)) => {
+            println!("Part 2: {}", index);
+            println!("Memory: {}", usage);
+        }
+        Err(e) => println!("Part 2: skipped, {}", e),
+    }
+
+    if let Some(needles) = &options.find {
+        let needles: Vec<&str> = needles.iter().map(String::as_str).collect();
+        let limit = options.limit.unwrap_or(100_000_000);
+
+        let occurrences = find_all(config, &needles, limit).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
 
-    println!("Part 2: {}", part2(input.trim()));
+        for occurrence in occurrences {
+            println!(
+                "{} after {} recipes",
+                needles[occurrence.needle], occurrence.index
+            );
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{part1, part2};
+    use super::*;
 
     #[test]
     fn p1_after_9() {
-        assert_eq!("5158916779", part1(9));
+        assert_eq!("5158916779", part1(&Config::default(), 9));
     }
 
     #[test]
     fn p1_after_5() {
-        assert_eq!("0124515891", part1(5))
+        assert_eq!("0124515891", part1(&Config::default(), 5))
     }
 
     #[test]
     fn p1_after_18() {
-        assert_eq!("9251071085", part1(18));
+        assert_eq!("9251071085", part1(&Config::default(), 18));
     }
 
     #[test]
     fn p1_after_2018() {
-        assert_eq!("5941429882", part1(2018));
+        assert_eq!("5941429882", part1(&Config::default(), 2018));
     }
 
     #[test]
     fn p2_after_9() {
-        assert_eq!(9, part2("51589"));
+        assert_eq!(9, part2(&Config::default(), "51589").unwrap().0);
     }
 
     #[test]
     fn p2_after_5() {
-        assert_eq!(5, part2("01245"))
+        assert_eq!(5, part2(&Config::default(), "01245").unwrap().0)
     }
 
     #[test]
     fn p2_after_18() {
-        assert_eq!(18, part2("92510"));
+        assert_eq!(18, part2(&Config::default(), "92510").unwrap().0);
     }
 
     #[test]
     fn p2_after_2018() {
-        assert_eq!(2018, part2("59414"));
+        assert_eq!(2018, part2(&Config::default(), "59414").unwrap().0);
+    }
+
+    fn parse_digits(s: &str) -> Vec<u8> {
+        Config::default().parse_digits(s).unwrap()
+    }
+
+    fn indexes(needles: &[&str], haystack: &str) -> Vec<(usize, usize)> {
+        let needles: Vec<Vec<u8>> = needles.iter().map(|n| parse_digits(n)).collect();
+        let matcher = Matcher::new(&needles, 10);
+
+        matcher
+            .matches(parse_digits(haystack).into_iter())
+            .map(|o| (o.needle, o.index))
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_prefix() {
+        // A naive counter resets on the second 0 and misses this one.
+        assert_eq!(vec![(0, 2)], indexes(&["01012"], "0101012"));
+        assert_eq!(vec![(0, 1)], indexes(&["112"], "1112"));
+    }
+
+    #[test]
+    fn overlapping_occurrences() {
+        assert_eq!(vec![(0, 0), (0, 1), (0, 2)], indexes(&["11"], "1111"));
+        assert_eq!(vec![(0, 0), (0, 2)], indexes(&["121"], "12121"));
+    }
+
+    #[test]
+    fn multiple_needles() {
+        assert_eq!(
+            vec![(1, 1), (0, 0), (2, 3)],
+            indexes(&["123", "2", "34"], "12334")
+        );
+    }
+
+    #[test]
+    fn find_all_examples() {
+        let occurrences = find_all(
+            &Config::default(),
+            &["51589", "01245", "92510", "59414"],
+            2030,
+        )
+        .unwrap();
+
+        assert!(occurrences.contains(&Occurrence {
+            needle: 0,
+            index: 9
+        }));
+        assert!(occurrences.contains(&Occurrence {
+            needle: 1,
+            index: 5
+        }));
+        assert!(occurrences.contains(&Occurrence {
+            needle: 2,
+            index: 18
+        }));
+        assert!(occurrences.contains(&Occurrence {
+            needle: 3,
+            index: 2018
+        }));
+        assert!(occurrences.iter().all(|o| o.index + 5 <= 2030));
+    }
+
+    #[test]
+    fn p2_overlapping_needle() {
+        // The first scores are 3710101245158916. `1012` follows `10` right
+        // after a partial match, which resetting the counter would skip.
+        assert_eq!(4, part2(&Config::default(), "1012").unwrap().0);
+        assert_eq!(2, part2(&Config::default(), "10101").unwrap().0);
+    }
+
+    fn config(initial: &[u8], elves: usize, base: u8) -> Config {
+        Config {
+            initial: initial.to_vec(),
+            elves,
+            base,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn default_scores() {
+        let scores: Vec<u8> = RecipeScores::new(&Config::default()).take(20).collect();
+        assert_eq!(parse_digits("37101012451589167792"), scores);
+    }
+
+    #[test]
+    fn single_elf_zeroes() {
+        // One elf with a zero only ever creates zeroes.
+        let config = config(&[0], 1, 10);
+        assert!(RecipeScores::new(&config).take(100).all(|s| s == 0));
+        assert_eq!(0, part2(&config, "0000").unwrap().0);
+        assert_eq!(
+            vec![0, 1, 2],
+            find_all(&config, &["000"], 5)
+                .unwrap()
+                .iter()
+                .map(|o| o.index)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn binary_base() {
+        // 1 + 1 = 10 in base 2, every sum is split into binary digits.
+        let config = config(&[1, 1], 2, 2);
+        let scores: Vec<u8> = RecipeScores::new(&config).take(6).collect();
+        assert_eq!(vec![1, 1, 1, 0, 1, 1], scores);
+        assert_eq!("10", &part1(&config, 2)[..2]);
+    }
+
+    #[test]
+    fn three_elves() {
+        let config = config(&[3, 7, 5], 3, 10);
+        let scores: Vec<u8> = RecipeScores::new(&config).take(5).collect();
+
+        // 3 + 7 + 5 = 15, then the elves step to recipes 4, 4 and 3 (wrapping
+        // around the five scores) and pick 5 + 5 + 1 = 11.
+        assert_eq!(vec![3, 7, 5, 1, 5], scores);
+        assert_eq!(Some(1), RecipeScores::new(&config).nth(5));
+    }
+
+    #[test]
+    fn hex_digits() {
+        let config = config(&[0xf, 0xf], 2, 16);
+        assert!(part1(&config, 0).starts_with("ff1e"));
+        assert_eq!(Ok(vec![0xa, 0xb]), config.parse_digits("ab"));
+        assert!(config.parse_digits("g").is_err());
+    }
+
+    #[test]
+    fn matcher_agrees_with_naive_search() {
+        let configs = [
+            Config::default(),
+            config(&[1, 2, 3], 3, 10),
+            config(&[1, 0], 2, 2),
+            config(&[2, 1], 2, 3),
+        ];
+
+        for config in &configs {
+            let scores: Vec<u8> = RecipeScores::new(config).take(3000).collect();
+
+            // Needles taken from the sequence itself, so they do occur.
+            for &(start, len) in &[(100, 3), (2000, 6), (17, 1), (1500, 12)] {
+                let needle = &scores[start..start + len];
+                let text: String = needle.iter().map(|&d| config.digit_char(d)).collect();
+
+                let naive = scores.windows(len).position(|w| w == needle).unwrap();
+                assert_eq!(
+                    naive,
+                    part2(config, &text).unwrap().0,
+                    "{:?} {}",
+                    config,
+                    text
+                );
+
+                let all: Vec<usize> = find_all(config, &[&text], 3000)
+                    .unwrap()
+                    .iter()
+                    .map(|o| o.index)
+                    .collect();
+                let naive_all: Vec<usize> = scores
+                    .windows(len)
+                    .enumerate()
+                    .filter(|(_, w)| *w == needle)
+                    .map(|(i, _)| i)
+                    .collect();
+                assert_eq!(naive_all, all);
+            }
+        }
+    }
+
+    #[test]
+    fn storage_backends_agree() {
+        let expected = part2(&Config::default(), "59414").unwrap().0;
+
+        for &(packing, memory_limit) in &[
+            (Some(Packing::Bytes), None),
+            (Some(Packing::Nibbles), None),
+            (None, Some(256)),
+        ] {
+            let config = Config {
+                packing,
+                memory_limit,
+                ..Config::default()
+            };
+            let (index, usage) = part2(&config, "59414").unwrap();
+
+            assert_eq!(expected, index);
+            assert_eq!(memory_limit.is_some(), usage.mapped.is_some());
+            assert!(usage.heap <= memory_limit.unwrap_or(usize::MAX));
+        }
+
+        let packed = Config {
+            packing: Some(Packing::Nibbles),
+            ..config(&[1, 2], 2, 17)
+        };
+        assert!(packed.validate().is_err());
+        assert_eq!(Packing::Bytes, config(&[1, 2], 2, 17).packing());
+    }
+
+    #[test]
+    fn options() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let options = Options::from_args(
+            args("--scores 1,2,3 --elves 3 --base 4 --find 12,3 --limit 50").into_iter(),
+        )
+        .unwrap();
+        assert_eq!(config(&[1, 2, 3], 3, 4), options.config);
+        assert_eq!(Some(vec!["12".to_string(), "3".to_string()]), options.find);
+        assert_eq!(Some(50), options.limit);
+
+        assert!(Options::from_args(args("--elves 3").into_iter()).is_err());
+        assert!(Options::from_args(args("--base 8 --scores 9,1").into_iter()).is_err());
+        assert!(Options::from_args(args("--base 1").into_iter()).is_err());
+        assert!(Options::from_args(args("--elves").into_iter()).is_err());
+
+        let options =
+            Options::from_args(args("--packing bytes --max-memory 4096").into_iter()).unwrap();
+        assert_eq!(Some(Packing::Bytes), options.config.packing);
+        assert_eq!(Some(4096), options.config.memory_limit);
+        assert!(Options::from_args(args("--packing bits").into_iter()).is_err());
+
+        // More elves than fit in a score.
+        let scores = vec!["1"; 300].join(",");
+        let options =
+            Options::from_args(args(&format!("--scores {} --elves 300", scores)).into_iter())
+                .unwrap();
+        assert_eq!(300, options.config.elves);
+    }
+
+    #[test]
+    fn needle_in_other_base() {
+        let config = config(&[1, 1], 2, 2);
+        assert!(part2(&config, "2").is_err());
+        assert_eq!("1000000000", part1(&config, 5));
     }
 }
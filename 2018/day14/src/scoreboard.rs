@@ -0,0 +1,386 @@
+//! Storage for the recipe scores. Part 2 needs tens of millions of them, so
+//! scores below 16 can be packed two to a byte, and the whole board can be
+//! moved to a memory mapped temporary file once it outgrows a memory limit.
+//! A spilled board keeps nothing on the heap, so any limit holds.
+
+use std::io;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Packing {
+    /// One score per byte.
+    Bytes,
+    /// Two scores per byte, for bases up to 16.
+    Nibbles,
+}
+
+impl Packing {
+    fn bytes_for(self, scores: usize) -> usize {
+        match self {
+            Packing::Bytes => scores,
+            Packing::Nibbles => scores.div_ceil(2),
+        }
+    }
+}
+
+pub struct Scoreboard {
+    packing: Packing,
+    bytes: Bytes,
+    len: usize,
+    /// Heap bytes the board may use before it spills to a file.
+    memory_limit: Option<usize>,
+    peak_heap: usize,
+}
+
+impl Scoreboard {
+    pub fn new(packing: Packing, memory_limit: Option<usize>) -> Self {
+        Scoreboard {
+            packing,
+            bytes: Bytes::Heap(Vec::new()),
+            len: 0,
+            memory_limit,
+            peak_heap: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, i: usize) -> u8 {
+        assert!(i < self.len, "Score {} out of range 0..{}", i, self.len);
+        let bytes = self.bytes.as_slice();
+
+        match self.packing {
+            Packing::Bytes => bytes[i],
+            Packing::Nibbles => bytes[i / 2] >> (4 * (i % 2)) & 0xf,
+        }
+    }
+
+    pub fn push(&mut self, score: u8) {
+        let needed = self.packing.bytes_for(self.len + 1);
+        self.reserve(needed).expect("Failed to grow scoreboard");
+
+        match self.packing {
+            Packing::Bytes => self.bytes.set(self.len, score),
+            Packing::Nibbles => {
+                assert!(score < 16, "Score {} does not fit in a nibble", score);
+                let i = self.len / 2;
+
+                if self.len.is_multiple_of(2) {
+                    self.bytes.set(i, score);
+                } else {
+                    let low = self.bytes.as_slice()[i];
+                    self.bytes.set(i, low | score << 4);
+                }
+            }
+        }
+
+        self.len += 1;
+    }
+
+    /// Make sure there are at least `needed` bytes, spilling to a file if
+    /// growing on the heap would break the memory limit.
+    fn reserve(&mut self, needed: usize) -> io::Result<()> {
+        match &mut self.bytes {
+            Bytes::Heap(heap) if needed > heap.capacity() => {
+                let wanted = needed.max(heap.capacity() * 2);
+
+                // The old and the new buffer are both allocated while the
+                // scores are copied across.
+                match self.memory_limit {
+                    Some(limit) if heap.capacity() + wanted > limit => {
+                        self.bytes = Bytes::Mapped(mapped::File::copy_of(heap, wanted)?);
+                    }
+                    _ => {
+                        let mut grown = Vec::with_capacity(wanted);
+                        grown.extend_from_slice(heap);
+                        self.peak_heap = self.peak_heap.max(heap.capacity() + grown.capacity());
+                        *heap = grown;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        match &mut self.bytes {
+            Bytes::Heap(heap) => {
+                if heap.len() < needed {
+                    heap.resize(needed, 0);
+                }
+            }
+            Bytes::Mapped(file) => {
+                if file.len() < needed {
+                    file.grow(needed.max(file.len() * 2))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Largest number of bytes the board has had allocated on the heap at
+    /// once.
+    pub fn peak_heap(&self) -> usize {
+        self.peak_heap
+    }
+
+    /// Bytes currently mapped from the spill file, if any.
+    pub fn mapped(&self) -> Option<usize> {
+        match &self.bytes {
+            Bytes::Heap(_) => None,
+            Bytes::Mapped(file) => Some(file.len()),
+        }
+    }
+}
+
+enum Bytes {
+    Heap(Vec<u8>),
+    Mapped(mapped::File),
+}
+
+impl Bytes {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Bytes::Heap(heap) => heap,
+            Bytes::Mapped(file) => file.as_slice(),
+        }
+    }
+
+    fn set(&mut self, i: usize, byte: u8) {
+        match self {
+            Bytes::Heap(heap) => heap[i] = byte,
+            Bytes::Mapped(file) => file.as_mut_slice()[i] = byte,
+        }
+    }
+}
+
+/// Peak resident memory of the whole process, where the platform tells.
+pub fn peak_resident() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kb * 1024)
+}
+
+#[cfg(unix)]
+mod mapped {
+    use std::fs::{self, OpenOptions};
+    use std::io;
+    use std::os::raw::{c_int, c_void};
+    use std::os::unix::io::AsRawFd;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Just the two calls we need, rather than a dependency on the libc crate.
+    // The flag values and the 64 bit `off_t` offset are the same on Linux,
+    // the BSDs and macOS.
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_SHARED: c_int = 1;
+
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+
+    /// A temporary file mapped read-write into memory. The file is unlinked
+    /// as soon as it is created, so nothing is left behind.
+    pub struct File {
+        file: fs::File,
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    impl File {
+        /// New mapping of `len` bytes, starting with the contents of `bytes`.
+        pub fn copy_of(bytes: &[u8], len: usize) -> io::Result<Self> {
+            let path = std::env::temp_dir().join(format!(
+                "day14-scores-{}-{}",
+                std::process::id(),
+                FILES.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            fs::remove_file(&path)?;
+
+            let mut mapped = File {
+                file,
+                ptr: std::ptr::null_mut(),
+                len: 0,
+            };
+            mapped.grow(len.max(bytes.len()))?;
+            mapped.as_mut_slice()[..bytes.len()].copy_from_slice(bytes);
+
+            Ok(mapped)
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        /// Extend the file to `len` bytes and map all of it again.
+        pub fn grow(&mut self, len: usize) -> io::Result<()> {
+            self.file.set_len(len as u64)?;
+            self.unmap();
+
+            // SAFETY: the file is open read-write and at least `len` bytes
+            // long. Nothing else has the unlinked file open, so the mapping
+            // is only ever accessed through this struct.
+            let ptr = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED,
+                    self.file.as_raw_fd(),
+                    0,
+                )
+            };
+
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            self.ptr = ptr as *mut u8;
+            self.len = len;
+            Ok(())
+        }
+
+        fn unmap(&mut self) {
+            if !self.ptr.is_null() {
+                // SAFETY: `ptr` and `len` come from a successful `mmap`, and
+                // no slices borrowed from it outlive `&mut self`.
+                unsafe { munmap(self.ptr as *mut c_void, self.len) };
+                self.ptr = std::ptr::null_mut();
+                self.len = 0;
+            }
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: `ptr` points to `len` mapped, initialised bytes.
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            // SAFETY: as above, and `&mut self` makes the access exclusive.
+            unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+    }
+
+    impl Drop for File {
+        fn drop(&mut self) {
+            self.unmap();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod mapped {
+    use std::io;
+
+    pub struct File(std::convert::Infallible);
+
+    impl File {
+        pub fn copy_of(_: &[u8], _: usize) -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Spilling to a memory mapped file needs a unix platform",
+            ))
+        }
+
+        pub fn len(&self) -> usize {
+            match self.0 {}
+        }
+
+        pub fn grow(&mut self, _: usize) -> io::Result<()> {
+            match self.0 {}
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            match self.0 {}
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            match self.0 {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(board: &mut Scoreboard, n: usize) {
+        for i in 0..n {
+            board.push((i * 7 % 16) as u8);
+        }
+    }
+
+    fn check(board: &Scoreboard, n: usize) {
+        assert_eq!(n, board.len());
+        for i in 0..n {
+            assert_eq!((i * 7 % 16) as u8, board.get(i), "score {}", i);
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let mut board = Scoreboard::new(Packing::Bytes, None);
+        fill(&mut board, 1001);
+        check(&board, 1001);
+        assert!(board.peak_heap() >= 1001);
+    }
+
+    #[test]
+    fn nibbles() {
+        let mut board = Scoreboard::new(Packing::Nibbles, None);
+        fill(&mut board, 1001);
+        check(&board, 1001);
+        // 512 bytes, plus the 256 they were copied from.
+        assert_eq!(768, board.peak_heap());
+        assert_eq!(None, board.mapped());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn spill_at_once() {
+        let mut board = Scoreboard::new(Packing::Bytes, Some(0));
+        fill(&mut board, 5000);
+        check(&board, 5000);
+
+        assert_eq!(0, board.peak_heap());
+        assert!(board.mapped().unwrap() >= 5000);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn spill() {
+        let mut board = Scoreboard::new(Packing::Nibbles, Some(1000));
+        fill(&mut board, 100_001);
+        check(&board, 100_001);
+
+        assert!(board.peak_heap() <= 1000);
+        assert!(board.mapped().unwrap() >= 50_001);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn out_of_range() {
+        let mut board = Scoreboard::new(Packing::Nibbles, None);
+        board.push(1);
+        board.get(1);
+    }
+}